@@ -0,0 +1,293 @@
+//! Play many games with the bot and no screen, to see how fair the boards are:
+//!
+//! ```text
+//! rua bench --mode expert --games 10000 --seed 1 --strategy exact
+//! ```
+//!
+//! Game i is generated from seed + i, with the first click in the middle.
+
+use std::fmt::Write as _;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::bot::{self, Strategy};
+use crate::config::{GameConfig, GameMode, Neighbours, Topology, MOST_SHOWN};
+use crate::{map, Error, Result};
+
+pub const USAGE: &str =
+    "usage: rua bench [--mode simple|normal|hard] [--games N] [--seed S] [--strategy exact|patterns]\n                  [--topology square|torus|hex]\n                  [--neighbours king|cross|knight|ring|OFFSETS] [--stack 1-3]\n                  [--layers 1-9]";
+
+struct Options {
+    mode: GameMode,
+    games: usize,
+    seed: u64,
+    strategy: Strategy,
+    topology: Topology,
+    neighbours: Neighbours,
+    stack: usize,
+    layers: usize,
+}
+
+fn parse(args: &[String]) -> Result<Options> {
+    let mut options = Options {
+        mode: GameMode::Hard,
+        games: 1000,
+        seed: 1,
+        strategy: Strategy::Exact,
+        topology: Topology::Square,
+        neighbours: Neighbours::King,
+        stack: 1,
+        layers: 1,
+    };
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| Error::Usage(format!("{} needs a value\n{}", flag, USAGE)))?;
+        let bad = || Error::Usage(format!("bad value {} for {}\n{}", value, flag, USAGE));
+        match flag.as_str() {
+            "--mode" => options.mode = GameMode::from_name(value).ok_or_else(bad)?,
+            "--games" => options.games = value.parse().map_err(|_| bad())?,
+            "--seed" => options.seed = value.parse().map_err(|_| bad())?,
+            "--strategy" => options.strategy = Strategy::from_name(value).ok_or_else(bad)?,
+            "--topology" => options.topology = Topology::from_name(value).ok_or_else(bad)?,
+            "--neighbours" => options.neighbours = Neighbours::from_name(value).ok_or_else(bad)?,
+            "--stack" => {
+                options.stack = value
+                    .parse()
+                    .ok()
+                    .filter(|n| (1..=3).contains(n))
+                    .ok_or_else(bad)?
+            }
+            "--layers" => {
+                options.layers = value
+                    .parse()
+                    .ok()
+                    .filter(|n| (1..=9).contains(n))
+                    .ok_or_else(bad)?
+            }
+            _ => return Err(Error::Usage(format!("unknown flag {}\n{}", flag, USAGE))),
+        }
+    }
+    Ok(options)
+}
+
+/// One game played by the bot.
+struct Played {
+    won: bool,
+    guesses: usize,
+    three_bv: usize,
+}
+
+fn play(conf: &GameConfig, seed: u64, strategy: Strategy) -> Played {
+    let mut rng = StdRng::seed_from_u64(seed);
+    // The middle of the middle layer.
+    let start =
+        conf.layer_size() * (conf.layers / 2) + conf.width * (conf.height / 2) + conf.width / 2;
+    let mut map = map::gen_map(start, conf, &mut rng);
+    let three_bv = map::three_bv(conf, &map);
+    let mine_cells = map::mine_cells(&map);
+    let mut left_cover = conf.get_size();
+    let mut guesses = 0;
+    let mut last = start;
+    map = map::mine_map(start, conf, map, &mut left_cover);
+    while !map::is_exploded(&map) && left_cover > mine_cells {
+        let Some((opens, guess)) = bot::next_opens(conf, &map, strategy, last, &mut rng) else {
+            break;
+        };
+        guesses += guess as usize;
+        for pos in opens {
+            map = map::mine_map(pos, conf, map, &mut left_cover);
+            last = pos;
+        }
+    }
+    Played {
+        won: !map::is_exploded(&map) && left_cover == mine_cells,
+        guesses,
+        three_bv,
+    }
+}
+
+/// Play all the games on every core, in the order of their seeds.
+fn play_all(conf: &GameConfig, options: &Options, threads: usize) -> Vec<Played> {
+    let mut played: Vec<(usize, Played)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                scope.spawn(move || {
+                    (t..options.games)
+                        .step_by(threads)
+                        .map(|i| {
+                            let seed = options.seed.wrapping_add(i as u64);
+                            (i, play(conf, seed, options.strategy))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    played.sort_unstable_by_key(|(i, _)| *i);
+    played.into_iter().map(|(_, played)| played).collect()
+}
+
+fn percent(part: usize, all: usize) -> f64 {
+    100.0 * part as f64 / all.max(1) as f64
+}
+
+/// The 3BV of every game in ten buckets, one bar each.
+fn histogram(values: &[usize]) -> String {
+    let mut text = String::new();
+    let (Some(&min), Some(&max)) = (values.iter().min(), values.iter().max()) else {
+        return text;
+    };
+    let width = (max - min) / 10 + 1;
+    let mut buckets = vec![0usize; (max - min) / width + 1];
+    for v in values {
+        buckets[(v - min) / width] += 1;
+    }
+    let most = buckets.iter().copied().max().unwrap_or(1);
+    for (i, count) in buckets.iter().enumerate() {
+        let low = min + i * width;
+        let bar = "#".repeat((count * 40).div_ceil(most));
+        let _ = writeln!(
+            text,
+            "          {:>4}-{:<4} {:<40} {}",
+            low,
+            low + width - 1,
+            bar,
+            count
+        );
+    }
+    text
+}
+
+fn report(
+    conf: &GameConfig,
+    options: &Options,
+    played: &[Played],
+    time: Duration,
+    threads: usize,
+) -> String {
+    let games = played.len();
+    let won = played.iter().filter(|p| p.won).count();
+    let guesses: usize = played.iter().map(|p| p.guesses).sum();
+    let no_guess = played.iter().filter(|p| p.guesses == 0).count();
+    let mut bvs: Vec<usize> = played.iter().map(|p| p.three_bv).collect();
+    bvs.sort_unstable();
+    let mean = bvs.iter().sum::<usize>() as f64 / games.max(1) as f64;
+
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "mode      {} ({}, {} mines)",
+        options.mode,
+        conf.size_name(),
+        conf.bomb
+    );
+    let _ = writeln!(text, "strategy  {}", options.strategy);
+    let _ = writeln!(
+        text,
+        "topology  {}, {} neighbours",
+        conf.topology, conf.neighbours
+    );
+    if conf.stack > 1 {
+        let _ = writeln!(text, "stack     up to {} mines a cell", conf.stack);
+    }
+    let _ = writeln!(text, "games     {} from seed {}", games, options.seed);
+    let _ = writeln!(text, "won       {} ({:.1}%)", won, percent(won, games));
+    let _ = writeln!(
+        text,
+        "guesses   {:.2} a game, {} games ({:.1}%) without any",
+        guesses as f64 / games.max(1) as f64,
+        no_guess,
+        percent(no_guess, games)
+    );
+    if let (Some(min), Some(max)) = (bvs.first(), bvs.last()) {
+        let _ = writeln!(
+            text,
+            "3bv       min {}, median {}, mean {:.1}, max {}",
+            min,
+            bvs[games / 2],
+            mean,
+            max
+        );
+        text.push_str(&histogram(&bvs));
+    }
+    let _ = writeln!(
+        text,
+        "time      {:.1}s, {:.1} games/s on {} threads",
+        time.as_secs_f64(),
+        games as f64 / time.as_secs_f64().max(f64::EPSILON),
+        threads
+    );
+    text
+}
+
+/// Run the benchmark with the arguments after `bench`, and print the report.
+pub fn run(args: &[String]) -> Result<()> {
+    let options = parse(args)?;
+    // Each layer gets the mines of the mode, like new random games.
+    let mode = GameConfig::from_game_mode(options.mode);
+    let conf = GameConfig {
+        topology: options.topology,
+        neighbours: options.neighbours.clone(),
+        stack: options.stack,
+        layers: options.layers,
+        bomb: mode.bomb * options.layers,
+        ..mode
+    };
+    if conf.most_around() > MOST_SHOWN {
+        return Err(Error::Usage(format!(
+            "numbers would go up to {}, past {}\n{}",
+            conf.most_around(),
+            MOST_SHOWN,
+            USAGE
+        )));
+    }
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let start = Instant::now();
+    let played = play_all(&conf, &options, threads);
+    print!(
+        "{}",
+        report(&conf, &options, &played, start.elapsed(), threads)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{play_all, Options};
+    use crate::bot::Strategy;
+    use crate::config::{GameConfig, GameMode, Neighbours, Topology};
+
+    #[test]
+    fn same_seed_same_games() {
+        let conf = GameConfig::from_game_mode(GameMode::Simple);
+        let options = Options {
+            mode: GameMode::Simple,
+            games: 20,
+            seed: 7,
+            strategy: Strategy::Exact,
+            topology: Topology::Square,
+            neighbours: Neighbours::King,
+            stack: 1,
+            layers: 1,
+        };
+        let one = play_all(&conf, &options, 1);
+        let four = play_all(&conf, &options, 4);
+        assert_eq!(20, four.len());
+        for (a, b) in one.iter().zip(&four) {
+            assert_eq!(
+                (a.won, a.guesses, a.three_bv),
+                (b.won, b.guesses, b.three_bv)
+            );
+        }
+        assert!(one.iter().any(|p| p.won));
+    }
+}
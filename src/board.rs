@@ -0,0 +1,439 @@
+//! The text format of boards, one line per row and one char per cell:
+//!
+//! ```text
+//! .  a covered safe cell
+//! *  a covered mine
+//! o  an opened cell
+//! F  a flagged mine
+//! f  a flagged safe cell
+//! 2  a covered cell with 2 mines, 3 for 3, where cells hold several
+//! ```
+//!
+//! A board of layers has them one after the other, with an empty line between two layers.
+//! A board that is not a flat square one with the eight cells around as neighbours
+//! starts with a line naming its shape and its rule, e.g. `# torus knight`, `# hex`
+//! or `# -1,0 1,0` for offsets.
+//!
+//! Boards in this format can be loaded too, to play a position made by hand.
+//!
+//! A layout alone has a short code to share, safe in URLs, e.g. `9x9.AJAA...`:
+//! the width and height, then one bit a cell row by row, set for mines, in unpadded base64url.
+//! It keeps the mines themselves, so it still works when boards are generated differently.
+//! Where cells hold up to N mines it's e.g. `9x9x3.` and two bits a cell, the mines in it.
+//! With layers the most mines a cell and the layers follow, e.g. `9x9x1x3.`.
+//! The same words as the first line of the text come before the size, e.g. `torus.knight.9x9.`,
+//! with `_` between offsets.
+
+use std::fs;
+
+use crate::{config, map, Error, Result};
+
+/// The words naming the shape of a board and its rule, none for a flat square board
+/// with the usual rule. A hex board has no rule.
+fn shape_words(conf: &config::GameConfig) -> Vec<String> {
+    let mut words = vec![];
+    if conf.topology != config::Topology::Square {
+        words.push(conf.topology.to_string());
+    }
+    if conf.topology != config::Topology::Hex && conf.neighbours != config::Neighbours::King {
+        words.push(conf.neighbours.to_name());
+    }
+    words
+}
+
+/// Read the words of `shape_words`, the topology first.
+fn read_shape(words: &[&str]) -> Option<(config::Topology, config::Neighbours)> {
+    let (topology, rule) = match words.split_first() {
+        Some((first, rest)) => match config::Topology::from_name(first) {
+            Some(topology) => (topology, rest),
+            None => (config::Topology::Square, words),
+        },
+        None => (config::Topology::Square, words),
+    };
+    let neighbours = match rule {
+        [] => config::Neighbours::King,
+        _ if topology == config::Topology::Hex => return None,
+        _ => config::Neighbours::from_name(&rule.join(" "))?,
+    };
+    Some((topology, neighbours))
+}
+
+/// Turn a map into the board text format.
+pub fn to_text(conf: &config::GameConfig, map: &[map::Cell]) -> String {
+    let mut text = String::new();
+    let words = shape_words(conf);
+    if !words.is_empty() {
+        text.push_str(&format!("# {}\n", words.join(" ")));
+    }
+    for (idx, row) in map.chunks(conf.width).enumerate() {
+        if idx > 0 && idx % conf.height == 0 {
+            text.push('\n');
+        }
+        for cell in row {
+            // A cell of several mines keeps its mines, not its flags.
+            text.push(match (&cell.surface, cell.content.count()) {
+                (map::Surface::Open, _) => 'o',
+                (_, n) if cell.content.is_mine() && n > 1 => map::glyph(n as u8),
+                (map::Surface::Cover, _) if cell.content.is_mine() => '*',
+                (map::Surface::Cover, _) => '.',
+                (map::Surface::Flag(_), _) if cell.content.is_mine() => 'F',
+                (map::Surface::Flag(_), _) => 'f',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Read a map from the board text format, the mine count is the number of mines in it.
+/// Cells hold as many mines as the fullest one.
+pub fn from_text(text: &str) -> Result<(config::GameConfig, Vec<map::Cell>)> {
+    let mut layers: Vec<Vec<&str>> = vec![];
+    let mut shape = None;
+    let mut apart = true;
+    for line in text.lines().map(str::trim_end) {
+        if let Some(words) = line.strip_prefix('#') {
+            if shape.is_some() || !layers.is_empty() {
+                return Err(Error::Save("the shape goes on the first line".to_string()));
+            }
+            let words: Vec<&str> = words.split_whitespace().collect();
+            shape = Some(
+                read_shape(&words)
+                    .ok_or_else(|| Error::Save(format!("unknown shape {:?}", words.join(" "))))?,
+            );
+            continue;
+        }
+        if line.is_empty() {
+            apart = true;
+            continue;
+        }
+        if apart {
+            layers.push(vec![]);
+            apart = false;
+        }
+        layers.last_mut().unwrap().push(line);
+    }
+    let height = layers.first().map_or(0, Vec::len);
+    if let Some(idx) = layers.iter().position(|layer| layer.len() != height) {
+        let msg = format!(
+            "layer {} has {} rows, not {}",
+            idx + 1,
+            layers[idx].len(),
+            height
+        );
+        return Err(Error::Save(msg));
+    }
+    let rows = layers.concat();
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    if width == 0 {
+        return Err(Error::Save("the board is empty".to_string()));
+    }
+    let (mut mines, mut surfaces) = (vec![], vec![]);
+    for (idx, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            let msg = format!(
+                "row {} has {} cells, not {}",
+                idx + 1,
+                row.chars().count(),
+                width
+            );
+            return Err(Error::Save(msg));
+        }
+        for ch in row.chars() {
+            let (mine, surface) = match ch {
+                '.' => (0, map::Surface::Cover),
+                '*' => (1, map::Surface::Cover),
+                'o' => (0, map::Surface::Open),
+                'F' => (1, map::Surface::Flag(1)),
+                'f' => (0, map::Surface::Flag(1)),
+                '2' | '3' => (ch as u8 - b'0', map::Surface::Cover),
+                _ => {
+                    return Err(Error::Save(format!(
+                        "unknown cell {:?} in row {}",
+                        ch,
+                        idx + 1
+                    )))
+                }
+            };
+            mines.push(mine);
+            surfaces.push(surface);
+        }
+    }
+    let (topology, neighbours) = shape.unwrap_or_default();
+    let conf = config::GameConfig {
+        height,
+        width,
+        bomb: mines.iter().map(|&n| n as usize).sum(),
+        topology,
+        neighbours,
+        stack: mines.iter().max().map_or(1, |&n| n.max(1) as usize),
+        layers: layers.len(),
+    };
+    if conf.bomb == 0 || mines.iter().all(|&n| n > 0) {
+        return Err(Error::Save(
+            "a board needs both mines and safe cells".to_string(),
+        ));
+    }
+    if surfaces.iter().filter(|&s| s.is_flag()).count() > conf.bomb {
+        return Err(Error::Save("more flags than mines".to_string()));
+    }
+    let mut map = map::from_counts(&conf, &mines);
+    if map
+        .iter()
+        .any(|cell| !cell.content.is_mine() && cell.content.count() > config::MOST_SHOWN)
+    {
+        let msg = format!("a number past {} can't be shown", config::MOST_SHOWN);
+        return Err(Error::Save(msg));
+    }
+    for (cell, surface) in map.iter_mut().zip(surfaces) {
+        cell.surface = surface;
+    }
+    Ok((conf, map))
+}
+
+const CODE_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The code of the mines of a map.
+pub fn to_code(conf: &config::GameConfig, map: &[map::Cell]) -> String {
+    let bits = if conf.stack > 1 { 2 } else { 1 };
+    let mut bytes = vec![0u8; (map.len() * bits).div_ceil(8)];
+    for (idx, cell) in map.iter().enumerate() {
+        if cell.content.is_mine() {
+            let at = idx * bits;
+            bytes[at / 8] |= (cell.content.count() as u8) << (8 - bits - at % 8);
+        }
+    }
+    let mut code: String = shape_words(conf)
+        .iter()
+        .map(|word| format!("{}.", word.replace(' ', "_")))
+        .collect();
+    code.push_str(&format!("{}x{}.", conf.width, conf.height));
+    if conf.stack > 1 || conf.layers > 1 {
+        code.insert_str(code.len() - 1, &format!("x{}", conf.stack));
+    }
+    if conf.layers > 1 {
+        code.insert_str(code.len() - 1, &format!("x{}", conf.layers));
+    }
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            code.push(CODE_ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    code
+}
+
+/// Read a map from a code, every cell covered.
+pub fn from_code(code: &str) -> Result<(config::GameConfig, Vec<map::Cell>)> {
+    let bad = |msg: &str| Error::Save(format!("bad code {}: {}", code, msg));
+    let (head, bits) = code.trim().rsplit_once('.').ok_or_else(|| bad("no size"))?;
+    let (words, size) = head.rsplit_once('.').unwrap_or(("", head));
+    let words: Vec<String> = words
+        .split('.')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.replace('_', " "))
+        .collect();
+    let words: Vec<&str> = words.iter().flat_map(|word| word.split(' ')).collect();
+    read_shape(&words).ok_or_else(|| bad("unknown shape"))?;
+    let size: Vec<usize> = size
+        .split('x')
+        .map(|n| n.parse::<usize>().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(|| bad("no size"))?;
+    let (width, height, stack, layers) = match size[..] {
+        [width, height] => (width, height, 1, 1),
+        [width, height, stack] if (2..=3).contains(&stack) => (width, height, stack, 1),
+        [width, height, stack, layers] if (1..=3).contains(&stack) && layers > 1 => {
+            (width, height, stack, layers)
+        }
+        _ => return Err(bad("no size")),
+    };
+    let per_cell = if stack > 1 { 2 } else { 1 };
+    // Six bits a char, so a size past them is a bad code, not worth any arithmetic.
+    let cells = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(layers))
+        .filter(|&n| n > 0 && n <= bits.len() * 6 / per_cell)
+        .ok_or_else(|| bad("wrong size"))?;
+    let mut bytes = vec![];
+    let mut n = 0u32;
+    for (i, ch) in bits.bytes().enumerate() {
+        let v = CODE_ALPHABET
+            .iter()
+            .position(|&c| c == ch)
+            .ok_or_else(|| bad("unknown char"))?;
+        n = n << 6 | v as u32;
+        if i % 4 == 3 {
+            bytes.extend_from_slice(&n.to_be_bytes()[1..]);
+            n = 0;
+        }
+    }
+    // The last group of 2 or 3 chars holds 1 or 2 bytes.
+    match bits.len() % 4 {
+        0 => (),
+        2 => bytes.push((n >> 4) as u8),
+        3 => bytes.extend_from_slice(&((n >> 2) as u16).to_be_bytes()),
+        _ => return Err(bad("wrong length")),
+    }
+    if cells == 0 || bytes.len() != (cells * per_cell).div_ceil(8) {
+        return Err(bad("wrong length"));
+    }
+    let mut text = String::new();
+    if !words.is_empty() {
+        text.push_str(&format!("# {}\n", words.join(" ")));
+    }
+    for idx in 0..cells {
+        let at = idx * per_cell;
+        let mines = bytes[at / 8] >> (8 - per_cell - at % 8) & ((1 << per_cell) - 1);
+        text.push(match mines {
+            0 => '.',
+            1 => '*',
+            n => map::glyph(n),
+        });
+        if idx % width == width - 1 {
+            text.push('\n');
+        }
+        // An empty line between two layers.
+        if idx % (width * height) == width * height - 1 {
+            text.push('\n');
+        }
+    }
+    let (conf, map) = from_text(&text)?;
+    Ok((config::GameConfig { stack, ..conf }, map))
+}
+
+/// Load a map from a file in the board text format.
+pub fn load(path: &str) -> Result<(config::GameConfig, Vec<map::Cell>)> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| Error::Save(format!("cannot read {}: {}", path, e)))?;
+    from_text(&text).map_err(|e| match e {
+        Error::Save(msg) => Error::Save(format!("{}: {}", path, msg)),
+        e => e,
+    })
+}
+
+/// The ".txt" files in the current directory that are boards, sorted by name.
+pub fn list() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(".") else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".txt") && load(name).is_ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Save a map to a file named `name`, with ".txt" if it has no extension.
+/// Return the path written.
+pub fn save(name: &str, conf: &config::GameConfig, map: &[map::Cell]) -> Result<String> {
+    let path = if name.contains('.') {
+        name.to_string()
+    } else {
+        format!("{}.txt", name)
+    };
+    fs::write(&path, to_text(conf, map))
+        .map_err(|e| Error::Save(format!("cannot write {}: {}", path, e)))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_code, from_text, to_code, to_text};
+    use crate::{config, map};
+
+    #[test]
+    fn text_round_trip() {
+        let text = "*.o\nFfo\n";
+        let (conf, map) = from_text(text).unwrap();
+        assert_eq!((2, 3, 2), (conf.height, conf.width, conf.bomb));
+        assert_eq!(text, to_text(&conf, &map));
+    }
+
+    #[test]
+    fn code_round_trip() {
+        for text in ["*.\n", "*..\n.*.\n..*\n", "..........*\n"] {
+            let (conf, map) = from_text(text).unwrap();
+            let code = to_code(&conf, &map);
+            assert!(code
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"x.-_".contains(&b)));
+            let (conf, map) = from_code(&code).unwrap();
+            assert_eq!(text, to_text(&conf, &map));
+        }
+        let (conf, map) = from_text("*..\n.*.\n..*").unwrap();
+        assert_eq!("3x3.iIA", to_code(&conf, &map));
+        assert!(from_code("3x3.iI!").is_err());
+        assert!(from_code("3x3.iI").is_err());
+        assert!(from_code("3x3.").is_err());
+        assert!(from_code("nonsense").is_err());
+        assert!(from_code("99999999999x99999999999.AA").is_err());
+        assert!(from_code("0x3.AA").is_err());
+        assert!(from_code("3x3x2x99999999999999999.AA").is_err());
+
+        // Cells with several mines take two bits each.
+        let (conf, map) = from_text("2.*\n..3\n").unwrap();
+        assert_eq!((6, 3), (conf.bomb, conf.stack));
+        let code = to_code(&conf, &map);
+        assert!(code.starts_with("3x2x3."));
+        let (conf, map) = from_code(&code).unwrap();
+        assert_eq!("2.*\n..3\n", to_text(&conf, &map));
+        // And layers come after them.
+        let text = "*.\n..\n\n.o\n.*\n";
+        let (conf, map) = from_text(text).unwrap();
+        assert_eq!((2, 2, 2), (conf.height, conf.width, conf.layers));
+        assert_eq!(text, to_text(&conf, &map));
+        let code = to_code(&conf, &map);
+        assert!(code.starts_with("2x2x1x2."));
+        let (conf, map) = from_code(&code).unwrap();
+        assert_eq!("*.\n..\n\n..\n.*\n", to_text(&conf, &map));
+    }
+
+    #[test]
+    fn shapes_round_trip() {
+        for (text, code) in [
+            ("# torus knight\n*..\n...\n..*\n", "torus.knight.3x3."),
+            ("# hex\n*..\n...\n..*\n", "hex.3x3."),
+            ("# -1,0 1,0\n*..\n...\n..*\n", "-1,0_1,0.3x3."),
+        ] {
+            let (conf, map) = from_text(text).unwrap();
+            assert_eq!(text, to_text(&conf, &map));
+            let made = to_code(&conf, &map);
+            assert!(made.starts_with(code), "{}", made);
+            let (back, map) = from_code(&made).unwrap();
+            assert_eq!(
+                (conf.topology, &conf.neighbours),
+                (back.topology, &back.neighbours)
+            );
+            assert_eq!(text, to_text(&back, &map));
+        }
+        // Numbers follow the rule: a knight's move away only.
+        let (conf, map) = from_text("# knight\n*..\n...\n...\n").unwrap();
+        assert_eq!(config::Neighbours::Knight, conf.neighbours);
+        assert_eq!(map::Content::Number(0), map[1].content);
+        assert_eq!(map::Content::Number(1), map[5].content);
+        assert!(from_text("# hex knight\n*.\n").is_err());
+        assert!(from_text("# nothing\n*.\n").is_err());
+        assert!(from_text("*.\n# torus\n").is_err());
+        assert!(from_code("nothing.3x3.iIA").is_err());
+        assert!(from_code("hex.knight.3x3.iIA").is_err());
+    }
+
+    #[test]
+    fn bad_boards() {
+        assert!(from_text("").is_err());
+        assert!(from_text("*.\n.").is_err());
+        assert!(from_text("*x").is_err());
+        assert!(from_text("..").is_err());
+        assert!(from_text("*ff").is_err());
+        assert!(from_text("*.\n..\n\n..\n").is_err());
+        // Every neighbour full through three layers, a 78 can't be shown.
+        assert!(from_text("333\n333\n333\n\n333\n3.3\n333\n\n333\n333\n333\n").is_err());
+        assert!(from_text("333\n3.3\n333\n").is_ok());
+    }
+}
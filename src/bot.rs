@@ -0,0 +1,173 @@
+//! A player that moves only from what it can see, for hints and autoplay.
+
+use std::fmt;
+
+use rand::Rng;
+
+use crate::{config, map, pattern, solver};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Open,
+    Flag,
+}
+
+/// One move, and the reason for it.
+pub struct Step {
+    pub action: Action,
+    pub cell: usize,
+    pub words: String,
+    /// The opened numbers the reason reads.
+    pub numbers: Vec<usize>,
+    /// Nothing proves the cell safe.
+    pub guess: bool,
+}
+
+/// Think of the next move, the nearest to `near` among equals.
+/// Safe cells go first, then mines to flag, then the least risky guess.
+pub fn think(conf: &config::GameConfig, map: &[map::Cell], near: usize) -> Option<Step> {
+    let (action, cell, what) = match solver::hint(conf, map, near)? {
+        solver::Hint::Safe(cell) => (Action::Open, cell, "safe"),
+        solver::Hint::Mine(cell) => (Action::Flag, cell, "a mine"),
+        solver::Hint::Guess(cell, p) => {
+            let name = map::cell_name(cell, conf);
+            return Some(Step {
+                action: Action::Open,
+                cell,
+                words: format!("Nothing is sure, {} is {:.0}%.", name, p * 100.0),
+                numbers: vec![],
+                guess: true,
+            });
+        }
+    };
+    let name = map::cell_name(cell, conf);
+    let (words, numbers) = match pattern::explain(conf, map, cell) {
+        Some(deduction) => {
+            let words = format!("{} is {}: {}.", name, what, deduction.pattern);
+            (words, deduction.numbers)
+        }
+        None => (
+            format!("{} is {}: every layout agrees.", name, what),
+            vec![],
+        ),
+    };
+    Some(Step {
+        action,
+        cell,
+        words,
+        numbers,
+        guess: false,
+    })
+}
+
+/// How the bot finds its moves when it plays fast and silent, e.g. in a benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Exact mine probabilities, guessing the least risky cell.
+    Exact,
+    /// Named patterns only, guessing a random cell when they find nothing.
+    Patterns,
+}
+
+impl Strategy {
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        match name {
+            "exact" => Some(Strategy::Exact),
+            "patterns" => Some(Strategy::Patterns),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Exact => write!(f, "exact"),
+            Strategy::Patterns => write!(f, "patterns"),
+        }
+    }
+}
+
+/// The cells to open next all at once, and if they are a guess.
+/// Mines are never flagged, nothing here trusts flags.
+pub fn next_opens(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    strategy: Strategy,
+    near: usize,
+    rng: &mut impl Rng,
+) -> Option<(Vec<usize>, bool)> {
+    let covered = |pos: &usize| map[*pos].surface == map::Surface::Cover;
+    match strategy {
+        Strategy::Exact => {
+            let probs = solver::probabilities(conf, map);
+            let safe: Vec<usize> = (0..map.len())
+                .filter(covered)
+                .filter(|&pos| probs[pos].is_some_and(solver::is_safe))
+                .collect();
+            if !safe.is_empty() {
+                return Some((safe, false));
+            }
+            // Proved mines are never opened, so their cells are left out.
+            let probs: Vec<Option<f64>> = probs
+                .into_iter()
+                .map(|p| p.filter(|&p| !solver::is_mine(p)))
+                .collect();
+            match solver::hint_from(conf, map, &probs, near)? {
+                solver::Hint::Guess(pos, _) | solver::Hint::Safe(pos) => Some((vec![pos], true)),
+                solver::Hint::Mine(_) => None,
+            }
+        }
+        Strategy::Patterns => {
+            if let Some(deduction) = pattern::next_safe(conf, map) {
+                let safe: Vec<usize> = deduction.safe.into_iter().filter(covered).collect();
+                if !safe.is_empty() {
+                    return Some((safe, false));
+                }
+            }
+            let left: Vec<usize> = (0..map.len()).filter(covered).collect();
+            if left.is_empty() {
+                return None;
+            }
+            Some((vec![left[rng.gen_range(0..left.len())]], true))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{think, Action};
+    use crate::config::GameConfig;
+    use crate::map::{self, Surface};
+
+    /// Play whole games, the bot never dies on a sure move and a game ends won or lost.
+    #[test]
+    fn plays_fair_games() {
+        let conf = GameConfig::sized(9, 9, 10);
+        for seed in 0..20 {
+            let start = conf.get_size() / 2;
+            let mut map = map::gen_map(start, &conf, &mut StdRng::seed_from_u64(seed));
+            let mut left_cover = conf.get_size();
+            let mut flag_num = 0;
+            map = map::mine_map(start, &conf, map, &mut left_cover);
+            while !map::is_exploded(&map) && left_cover > conf.bomb {
+                let opened = map.iter().filter(|c| c.surface == Surface::Open).count();
+                assert_eq!(conf.get_size() - opened, left_cover);
+
+                let step = think(&conf, &map, start).unwrap();
+                match step.action {
+                    Action::Open => {
+                        map = map::mine_map(step.cell, &conf, map, &mut left_cover);
+                        assert!(step.guess || !map::is_exploded(&map), "seed {}", seed);
+                    }
+                    Action::Flag => {
+                        map = map::flag_map(step.cell, &conf, map, &mut flag_num).0;
+                    }
+                }
+            }
+        }
+    }
+}
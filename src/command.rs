@@ -0,0 +1,208 @@
+use termion::event::Key;
+
+use crate::{config, map};
+
+/// The commands typed after `:` in a game.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Open(usize),
+    Flag(usize),
+    Chord(usize),
+    Goto(usize),
+    Hint,
+    Save(String),
+    Code,
+    Restart,
+    Quit,
+}
+
+/// All the command names, used by tab completion.
+pub const NAMES: [&str; 9] = [
+    "open", "flag", "chord", "goto", "hint", "save", "code", "restart", "quit",
+];
+
+fn cell_arg(arg: Option<&str>, conf: &config::GameConfig) -> Result<usize, String> {
+    let arg = arg.ok_or_else(|| "Which cell?".to_string())?;
+    map::parse_cell_name(arg, conf).ok_or_else(|| format!("No cell {}.", arg))
+}
+
+/// Parse one command line, e.g. "open C7", into a command.
+pub fn parse(line: &str, conf: &config::GameConfig) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or("");
+    let arg = words.next();
+    let cmd = match name {
+        "open" | "o" => Command::Open(cell_arg(arg, conf)?),
+        "flag" | "f" => Command::Flag(cell_arg(arg, conf)?),
+        "chord" | "c" => Command::Chord(cell_arg(arg, conf)?),
+        "goto" | "g" => Command::Goto(cell_arg(arg, conf)?),
+        "hint" => Command::Hint,
+        "save" => Command::Save(arg.ok_or_else(|| "Save as?".to_string())?.to_string()),
+        "code" => Command::Code,
+        "restart" => Command::Restart,
+        "quit" | "q" => Command::Quit,
+        "" => return Err("Nothing to do.".to_string()),
+        _ => return Err(format!("What's {}?", name)),
+    };
+    if words.next().is_some() {
+        return Err(format!("Too many words for {}.", name));
+    }
+    Ok(cmd)
+}
+
+/// What the prompt wants after a key.
+pub enum PromptAction {
+    Edit,
+    Submit(String),
+    Cancel,
+}
+
+/// A line editor with history and tab completion.
+#[derive(Default)]
+pub struct Prompt {
+    line: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    history_idx: usize,
+}
+
+impl Prompt {
+    /// Start editing an empty line.
+    pub fn start(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.history_idx = self.history.len();
+    }
+
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// The cursor position inside the line.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn set_line(&mut self, line: &str) {
+        self.line = line.chars().collect();
+        self.cursor = self.line.len();
+    }
+
+    pub fn key(&mut self, key: Key) -> PromptAction {
+        match key {
+            Key::Char('\n') => {
+                let line = self.line();
+                if !line.trim().is_empty() && self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                }
+                return PromptAction::Submit(line);
+            }
+            Key::Char('\t') => self.complete(),
+            Key::Char(ch) => {
+                self.line.insert(self.cursor, ch);
+                self.cursor += 1;
+            }
+            Key::Esc | Key::Ctrl('c') => return PromptAction::Cancel,
+            Key::Backspace if self.cursor == 0 && self.line.is_empty() => {
+                return PromptAction::Cancel
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.line.len(),
+            Key::Up if self.history_idx > 0 => {
+                self.history_idx -= 1;
+                let line = self.history[self.history_idx].clone();
+                self.set_line(&line);
+            }
+            Key::Down if self.history_idx < self.history.len() => {
+                self.history_idx += 1;
+                let line = self.history.get(self.history_idx).cloned();
+                self.set_line(&line.unwrap_or_default());
+            }
+            _ => (),
+        }
+        PromptAction::Edit
+    }
+
+    /// Complete the command name before cursor, as far as it's unambiguous.
+    fn complete(&mut self) {
+        let head: String = self.line[..self.cursor].iter().collect();
+        if head.contains(char::is_whitespace) {
+            return;
+        }
+        let found: Vec<&str> = NAMES
+            .iter()
+            .copied()
+            .filter(|name| name.starts_with(&head))
+            .collect();
+        let Some(first) = found.first() else {
+            return;
+        };
+        let common = found.iter().fold(first.len(), |len, name| {
+            first
+                .chars()
+                .zip(name.chars())
+                .take(len)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        let mut done: String = first[..common].to_string();
+        if found.len() == 1 {
+            done.push(' ');
+        }
+        let rest: String = self.line[self.cursor..].iter().collect();
+        self.set_line(&done);
+        self.line.extend(rest.trim_start().chars());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Command, Prompt, PromptAction};
+    use crate::config::{GameConfig, GameMode};
+    use termion::event::Key;
+
+    #[test]
+    fn parse_commands() {
+        let conf = GameConfig::from_game_mode(GameMode::Simple);
+        assert_eq!(Ok(Command::Open(6 * 9 + 2)), parse("open C7", &conf));
+        assert_eq!(Ok(Command::Flag(0)), parse(" flag a1 ", &conf));
+        assert_eq!(
+            Ok(Command::Save("one".to_string())),
+            parse("save one", &conf)
+        );
+        assert!(parse("open Z9", &conf).is_err());
+        assert!(parse("jump A1", &conf).is_err());
+    }
+
+    #[test]
+    fn complete_and_recall() {
+        let mut prompt = Prompt::default();
+        prompt.start();
+        for key in [Key::Char('r'), Key::Char('e'), Key::Char('\t')] {
+            prompt.key(key);
+        }
+        assert_eq!("restart ", prompt.line());
+        prompt.start();
+        prompt.key(Key::Char('c'));
+        prompt.key(Key::Char('\t'));
+        assert_eq!("c", prompt.line());
+        prompt.key(Key::Char('o'));
+        prompt.key(Key::Char('\t'));
+        assert_eq!("code ", prompt.line());
+        assert!(
+            matches!(prompt.key(Key::Char('\n')), PromptAction::Submit(line) if line == "code ")
+        );
+        prompt.start();
+        prompt.key(Key::Up);
+        assert_eq!("code ", prompt.line());
+    }
+}
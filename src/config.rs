@@ -1,6 +1,9 @@
 #![allow(dead_code)]
 
-use std::env;
+use std::time::Duration;
+use std::{env, fmt};
+
+use crate::{Error, Result};
 
 /// The basic configs for whole program, just some keymaps for now.
 #[derive(Debug)]
@@ -12,26 +15,229 @@ pub struct GlobleConfig {
     pub mine_key: char,
     pub flag_key: char,
     pub quit_key: char,
+    pub command_key: char,
+    pub heat_key: char,
+    pub hint_key: char,
+    pub auto_key: char,
+    pub step_key: char,
+    pub faster_key: char,
+    pub slower_key: char,
+    pub prev_layer_key: char,
+    pub next_layer_key: char,
+    /// The delay between two moves of autoplay.
+    pub auto_delay: Duration,
+    /// Print plain text announcements instead of painting the screen.
+    pub accessible: bool,
+    /// Copy shared results to the clipboard, or else show them.
+    pub clipboard: bool,
+    /// How the cells of new random boards touch each other.
+    pub topology: Topology,
+    /// Which cells of new random boards count as neighbours.
+    pub neighbours: Neighbours,
+    /// The most mines a cell of new random boards can hold.
+    pub stack: usize,
+    /// How many layers new random boards stack, 1 for a flat board.
+    pub layers: usize,
 }
 
-fn env_or_into_char(key: &str, default: &str) -> char {
+impl GlobleConfig {
+    /// The config of a new random game of a difficulty, on the board shape chosen.
+    /// Each layer gets the mines of the difficulty, so the mines are as dense.
+    pub fn game_config(&self, mode: GameMode) -> GameConfig {
+        let conf = GameConfig::from_game_mode(mode);
+        GameConfig {
+            topology: self.topology,
+            neighbours: self.neighbours.clone(),
+            stack: self.stack,
+            layers: self.layers,
+            bomb: conf.bomb * self.layers,
+            ..conf
+        }
+    }
+}
+
+fn env_or_into_char(key: &str, default: &str) -> Result<char> {
     env::var(key)
         .unwrap_or_else(|_| default.to_string())
         .chars()
         .next()
-        .unwrap()
-}
-
-pub fn globle_config_from_env() -> GlobleConfig {
-    return GlobleConfig {
-        up_key: env_or_into_char("UP_KEY", "k"),
-        down_key: env_or_into_char("DOWN_KEY", "j"),
-        left_key: env_or_into_char("LEFT_KEY", "h"),
-        right_key: env_or_into_char("RIGHT_KEY", "l"),
-        mine_key: env_or_into_char("MINE_KEY", " "),
-        flag_key: env_or_into_char("FLAG_KEY", "f"),
-        quit_key: env_or_into_char("QUIT_KEY", "q"),
+        .ok_or_else(|| Error::Config(format!("{} should not be empty", key)))
+}
+
+fn env_or_millis(key: &str, default: u64) -> Result<Duration> {
+    match env::var(key) {
+        Ok(v) => v
+            .parse()
+            .map(Duration::from_millis)
+            .map_err(|_| Error::Config(format!("{} should be milliseconds, not {}", key, v))),
+        Err(_) => Ok(Duration::from_millis(default)),
+    }
+}
+
+pub fn globle_config_from_env() -> Result<GlobleConfig> {
+    let config = GlobleConfig {
+        up_key: env_or_into_char("UP_KEY", "k")?,
+        down_key: env_or_into_char("DOWN_KEY", "j")?,
+        left_key: env_or_into_char("LEFT_KEY", "h")?,
+        right_key: env_or_into_char("RIGHT_KEY", "l")?,
+        mine_key: env_or_into_char("MINE_KEY", " ")?,
+        flag_key: env_or_into_char("FLAG_KEY", "f")?,
+        quit_key: env_or_into_char("QUIT_KEY", "q")?,
+        command_key: env_or_into_char("COMMAND_KEY", ":")?,
+        heat_key: env_or_into_char("HEAT_KEY", "p")?,
+        hint_key: env_or_into_char("HINT_KEY", "?")?,
+        auto_key: env_or_into_char("AUTO_KEY", "a")?,
+        step_key: env_or_into_char("STEP_KEY", "n")?,
+        faster_key: env_or_into_char("FASTER_KEY", "+")?,
+        slower_key: env_or_into_char("SLOWER_KEY", "-")?,
+        prev_layer_key: env_or_into_char("PREV_LAYER_KEY", "[")?,
+        next_layer_key: env_or_into_char("NEXT_LAYER_KEY", "]")?,
+        auto_delay: env_or_millis("AUTO_DELAY", 300)?,
+        accessible: env::var("ACCESSIBLE").is_ok_and(|v| !v.is_empty() && v != "0"),
+        clipboard: env::var("CLIPBOARD").map_or(true, |v| v != "0"),
+        topology: match env::var("TOPOLOGY") {
+            Ok(v) => Topology::from_name(&v).ok_or_else(|| {
+                Error::Config(format!(
+                    "TOPOLOGY should be square, torus or hex, not {}",
+                    v
+                ))
+            })?,
+            Err(_) => Topology::Square,
+        },
+        neighbours: match env::var("NEIGHBOURS") {
+            Ok(v) => Neighbours::from_name(&v).ok_or_else(|| {
+                Error::Config(format!(
+                    "NEIGHBOURS should be king, cross, knight, ring, or offsets like \"-1,0 1,0\", not {}",
+                    v
+                ))
+            })?,
+            Err(_) => Neighbours::King,
+        },
+        stack: match env::var("STACK") {
+            Ok(v) => v
+                .parse()
+                .ok()
+                .filter(|n| (1..=3).contains(n))
+                .ok_or_else(|| Error::Config(format!("STACK should be 1 to 3, not {}", v)))?,
+            Err(_) => 1,
+        },
+        layers: match env::var("LAYERS") {
+            Ok(v) => v
+                .parse()
+                .ok()
+                .filter(|n| (1..=9).contains(n))
+                .ok_or_else(|| Error::Config(format!("LAYERS should be 1 to 9, not {}", v)))?,
+            Err(_) => 1,
+        },
     };
+    let most = config.game_config(GameMode::Simple).most_around();
+    if most > MOST_SHOWN {
+        return Err(Error::Config(format!(
+            "STACK, LAYERS and NEIGHBOURS make numbers up to {}, past {} they can't be shown",
+            most, MOST_SHOWN
+        )));
+    }
+    Ok(config)
+}
+
+/// The biggest number a cell can show, `z` in base 36.
+pub const MOST_SHOWN: usize = 35;
+
+/// How the cells of a board touch each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// A flat board with edges.
+    #[default]
+    Square,
+    /// The edges wrap around, the cells on one touch those on the other.
+    Torus,
+    /// Hexagons in offset rows, each odd row half a cell to the right, six neighbours a cell.
+    Hex,
+}
+
+impl Topology {
+    pub fn from_name(name: &str) -> Option<Topology> {
+        match name {
+            "square" => Some(Topology::Square),
+            "torus" => Some(Topology::Torus),
+            "hex" => Some(Topology::Hex),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Square => write!(f, "square"),
+            Topology::Torus => write!(f, "torus"),
+            Topology::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+/// Which cells count as the neighbours of a cell, the ones its number counts.
+/// A hex board has its own six and no rule.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Neighbours {
+    /// The eight cells around.
+    #[default]
+    King,
+    /// The four cells beside, above and below.
+    Cross,
+    /// The eight cells a knight's move away.
+    Knight,
+    /// The sixteen cells two steps away, between the 5x5 and the 3x3 squares.
+    Ring,
+    /// Offsets of (row, column), as many as wanted.
+    Custom(Vec<(isize, isize)>),
+}
+
+impl Neighbours {
+    /// Find a rule by its name, or read offsets like "-1,0 1,0 0,-1 0,1".
+    pub fn from_name(name: &str) -> Option<Neighbours> {
+        match name {
+            "king" => return Some(Neighbours::King),
+            "cross" => return Some(Neighbours::Cross),
+            "knight" => return Some(Neighbours::Knight),
+            "ring" => return Some(Neighbours::Ring),
+            _ => (),
+        }
+        let mut offsets = vec![];
+        for pair in name.split_whitespace() {
+            let (row, col) = pair.split_once(',')?;
+            let offset = (row.trim().parse().ok()?, col.trim().parse().ok()?);
+            if offset == (0, 0) || offsets.contains(&offset) {
+                return None;
+            }
+            offsets.push(offset);
+        }
+        (!offsets.is_empty()).then_some(Neighbours::Custom(offsets))
+    }
+
+    /// The name `from_name` reads back, the offsets themselves for a custom rule.
+    pub fn to_name(&self) -> String {
+        match self {
+            Neighbours::Custom(offsets) => offsets
+                .iter()
+                .map(|(row, col)| format!("{},{}", row, col))
+                .collect::<Vec<_>>()
+                .join(" "),
+            rule => rule.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Neighbours {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Neighbours::King => write!(f, "king"),
+            Neighbours::Cross => write!(f, "cross"),
+            Neighbours::Knight => write!(f, "knight"),
+            Neighbours::Ring => write!(f, "ring"),
+            Neighbours::Custom(_) => write!(f, "custom"),
+        }
+    }
 }
 
 /// The configs of game mode.
@@ -40,14 +246,62 @@ pub struct GameConfig {
     pub height: usize,
     pub width: usize,
     pub bomb: usize,
+    pub topology: Topology,
+    pub neighbours: Neighbours,
+    /// The most mines a cell can hold, 1 for the classic game.
+    pub stack: usize,
+    /// Layers of `height` rows stacked on each other, 1 for a flat board.
+    pub layers: usize,
 }
 
 impl GameConfig {
     pub fn get_size(&self) -> usize {
+        self.layers * self.layer_size()
+    }
+
+    /// The cells of one layer, the cells of a board go layer by layer, then row by row.
+    pub fn layer_size(&self) -> usize {
         self.height * self.width
     }
+
+    /// A flat square board of that size with the usual rules, for tests to start from.
+    #[cfg(test)]
+    pub fn sized(height: usize, width: usize, bomb: usize) -> GameConfig {
+        GameConfig {
+            height,
+            width,
+            bomb,
+            topology: Topology::Square,
+            neighbours: Neighbours::King,
+            stack: 1,
+            layers: 1,
+        }
+    }
+
+    /// The biggest number the rules allow: a full stack on every neighbour.
+    pub fn most_around(&self) -> usize {
+        let flat = match (self.topology, &self.neighbours) {
+            (Topology::Hex, _) => 6,
+            (_, Neighbours::King) | (_, Neighbours::Knight) => 8,
+            (_, Neighbours::Cross) => 4,
+            (_, Neighbours::Ring) => 16,
+            (_, Neighbours::Custom(offsets)) => offsets.len(),
+        };
+        // The layers right above and below add the cell straight there too.
+        let around = flat + (self.layers - 1).min(2) * (flat + 1);
+        self.stack * around
+    }
+
+    /// The size as players read it, e.g. "9x9", or "9x9x3" with layers.
+    pub fn size_name(&self) -> String {
+        match self.layers {
+            1 => format!("{}x{}", self.width, self.height),
+            layers => format!("{}x{}x{}", self.width, self.height, layers),
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum GameMode {
     Simple,
     Normal,
@@ -62,6 +316,16 @@ impl GameMode {
             _ => GameMode::Hard,
         }
     }
+
+    /// Find a mode by its name, or the name players know it by, e.g. "expert".
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        match name.to_lowercase().as_str() {
+            "simple" | "beginner" => Some(GameMode::Simple),
+            "normal" | "intermediate" => Some(GameMode::Normal),
+            "hard" | "expert" => Some(GameMode::Hard),
+            _ => None,
+        }
+    }
 }
 
 impl GameConfig {
@@ -72,28 +336,39 @@ impl GameConfig {
                 height: 9,
                 width: 9,
                 bomb: 10,
+                topology: Topology::Square,
+                neighbours: Neighbours::King,
+                stack: 1,
+                layers: 1,
             },
             GameMode::Normal => GameConfig {
                 height: 16,
                 width: 16,
                 bomb: 40,
+                topology: Topology::Square,
+                neighbours: Neighbours::King,
+                stack: 1,
+                layers: 1,
             },
             GameMode::Hard => GameConfig {
                 height: 16,
                 width: 30,
                 bomb: 99,
+                topology: Topology::Square,
+                neighbours: Neighbours::King,
+                stack: 1,
+                layers: 1,
             },
         }
     }
 }
 
-impl ToString for GameMode {
-    fn to_string(&self) -> String {
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            GameMode::Simple => "Simple",
-            GameMode::Normal => "Normal",
-            GameMode::Hard => "Hard",
+            GameMode::Simple => write!(f, "Simple"),
+            GameMode::Normal => write!(f, "Normal"),
+            GameMode::Hard => write!(f, "Hard"),
         }
-        .to_string()
     }
 }
@@ -0,0 +1,353 @@
+//! The daily challenge: the same board for everyone on a day and difficulty,
+//! with one ranked attempt kept in a history file.
+//!
+//! The history is `$XDG_DATA_HOME/rua/daily.txt` (`~/.local/share/rua/daily.txt` by default),
+//! one line per day and difficulty, e.g. `2026-10-19 hard won 123 0`:
+//! the date, the difficulty, how it ended, the seconds and the hints used.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::{env, fmt, fs};
+
+use crate::config::{GameConfig, GameMode};
+use crate::{map, Error, Result};
+
+/// A day of the calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Date {
+    fn parse(text: &str) -> Option<Date> {
+        let mut parts = text.split('-');
+        let date = Date {
+            year: parts.next()?.parse().ok()?,
+            month: parts.next()?.parse().ok()?,
+            day: parts.next()?.parse().ok()?,
+        };
+        Some(date)
+    }
+
+    /// Days since 1970-01-01, from Howard Hinnant's `days_from_civil`.
+    fn days(&self) -> i64 {
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let era = year.div_euclid(400);
+        let yoe = year - era * 400;
+        let month = self.month as i64;
+        let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+}
+
+/// The local date of today.
+pub fn today() -> Date {
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        libc::localtime_r(&now, &mut tm);
+    }
+    Date {
+        year: tm.tm_year as i64 + 1900,
+        month: tm.tm_mon as u32 + 1,
+        day: tm.tm_mday as u32,
+    }
+}
+
+fn mode_name(mode: GameMode) -> String {
+    mode.to_string().to_lowercase()
+}
+
+/// The seed of a day and difficulty, FNV-1a of e.g. "2026-10-19/hard",
+/// the same on every machine and every build.
+pub fn seed(date: &Date, mode: GameMode) -> u64 {
+    format!("{}/{}", date, mode_name(mode))
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// SplitMix64, written out here rather than taken from `rand`,
+/// whose generators and sampling may change with any version.
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number below n, by the high half of a 128-bit product.
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// The board of a day and difficulty, already opened in the middle,
+/// so the first click cannot make it differ between players.
+pub fn board(date: &Date, mode: GameMode) -> (GameConfig, Vec<map::Cell>) {
+    let conf = GameConfig::from_game_mode(mode);
+    let start = conf.width * (conf.height / 2) + conf.width / 2;
+    let mut rng = SplitMix(seed(date, mode));
+    // The first cells of a shuffle, never the middle one, get the mines.
+    let mut cells: Vec<usize> = (0..conf.get_size()).filter(|&pos| pos != start).collect();
+    for i in 0..conf.bomb {
+        let j = i + rng.below(cells.len() - i);
+        cells.swap(i, j);
+    }
+    let mut mines = vec![false; conf.get_size()];
+    for &pos in &cells[..conf.bomb] {
+        mines[pos] = true;
+    }
+    let mut left_cover = conf.get_size();
+    let board = map::mine_map(
+        start,
+        &conf,
+        map::from_mines(&conf, &mines),
+        &mut left_cover,
+    );
+    (conf, board)
+}
+
+/// A daily game being played.
+pub struct Challenge {
+    pub date: Date,
+    pub mode: GameMode,
+    /// The first attempt of the day, the only one kept.
+    pub ranked: bool,
+}
+
+/// One line of the history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub date: Date,
+    pub mode: String,
+    /// "won", "lost", or "left" for a game not finished.
+    pub outcome: String,
+    pub secs: u64,
+    pub hints: usize,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.date, self.mode, self.outcome, self.secs, self.hints
+        )
+    }
+}
+
+impl Record {
+    fn parse(line: &str) -> Option<Record> {
+        let mut words = line.split_whitespace();
+        Some(Record {
+            date: Date::parse(words.next()?)?,
+            mode: words.next()?.to_string(),
+            outcome: words.next()?.to_string(),
+            secs: words.next()?.parse().ok()?,
+            hints: words.next()?.parse().ok()?,
+        })
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    let dir = match env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var("HOME")
+                .map_err(|_| Error::Save("no HOME to keep the daily history in".to_string()))?;
+            PathBuf::from(home).join(".local/share")
+        }
+    };
+    Ok(dir.join("rua").join("daily.txt"))
+}
+
+/// Every record of the history, oldest first. Lines that cannot be read are skipped.
+pub fn history() -> Result<Vec<Record>> {
+    let path = path()?;
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(text.lines().filter_map(Record::parse).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(Error::Save(format!(
+            "cannot read {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+fn write_history(records: &[Record]) -> Result<()> {
+    let path = path()?;
+    let text: String = records.iter().map(|r| format!("{}\n", r)).collect();
+    let write = || {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, text)
+    };
+    write().map_err(|e| Error::Save(format!("cannot write {}: {}", path.display(), e)))
+}
+
+/// Today's record of a difficulty, if it's played already.
+pub fn played(records: &[Record], date: &Date, mode: GameMode) -> Option<Record> {
+    let mode = mode_name(mode);
+    records
+        .iter()
+        .find(|r| r.date == *date && r.mode == mode)
+        .cloned()
+}
+
+/// Start an attempt, return if it's the ranked one.
+/// The ranked attempt is kept as left until it's finished, so quitting uses it up.
+pub fn start(date: &Date, mode: GameMode) -> Result<bool> {
+    let mut records = history()?;
+    if played(&records, date, mode).is_some() {
+        return Ok(false);
+    }
+    records.push(Record {
+        date: *date,
+        mode: mode_name(mode),
+        outcome: "left".to_string(),
+        secs: 0,
+        hints: 0,
+    });
+    write_history(&records)?;
+    Ok(true)
+}
+
+/// Keep how the ranked attempt of a day ended.
+pub fn finish(date: &Date, mode: GameMode, outcome: &str, secs: u64, hints: usize) -> Result<()> {
+    let mut records = history()?;
+    let mode = mode_name(mode);
+    if let Some(record) = records
+        .iter_mut()
+        .find(|r| r.date == *date && r.mode == mode)
+    {
+        record.outcome = outcome.to_string();
+        record.secs = secs;
+        record.hints = hints;
+    }
+    write_history(&records)
+}
+
+/// The days in a row with a daily won, up to today, or up to yesterday
+/// while today is not won yet. A win with hints or the bot's help doesn't count.
+pub fn streak(records: &[Record], today: &Date) -> usize {
+    let mut won: Vec<i64> = records
+        .iter()
+        .filter(|r| r.outcome == "won" && r.hints == 0)
+        .map(|r| r.date.days())
+        .collect();
+    won.sort_unstable();
+    won.dedup();
+    let mut day = today.days();
+    if won.binary_search(&day).is_err() {
+        day -= 1;
+    }
+    let mut count = 0;
+    while won.binary_search(&(day - count as i64)).is_ok() {
+        count += 1;
+    }
+    count
+}
+
+/// The history and the streak in words, for `rua daily`.
+pub fn report(records: &[Record], today: &Date) -> String {
+    let mut text = String::new();
+    for record in records {
+        let _ = write!(
+            text,
+            "{}  {:<6} {:<4} {:>4}s",
+            record.date, record.mode, record.outcome, record.secs
+        );
+        if record.hints > 0 {
+            let _ = write!(text, "  {} hints", record.hints);
+        }
+        text.push('\n');
+    }
+    let _ = writeln!(text, "streak: {} days", streak(records, today));
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::{board, seed, streak, Date, Record, SplitMix};
+    use crate::config::GameMode;
+
+    fn won(date: &str) -> Record {
+        Record::parse(&format!("{} hard won 100 0", date)).unwrap()
+    }
+
+    #[test]
+    fn days_and_seeds() {
+        let date = |y, m, d| Date {
+            year: y,
+            month: m,
+            day: d,
+        };
+        assert_eq!(0, date(1970, 1, 1).days());
+        assert_eq!(1, date(2024, 3, 1).days() - date(2024, 2, 29).days());
+        assert_eq!(
+            seed(&date(2026, 10, 19), GameMode::Hard),
+            seed(&date(2026, 10, 19), GameMode::Hard)
+        );
+        assert_ne!(
+            seed(&date(2026, 10, 19), GameMode::Hard),
+            seed(&date(2026, 10, 19), GameMode::Simple)
+        );
+    }
+
+    #[test]
+    fn boards_never_change() {
+        // The reference output of SplitMix64 from 0.
+        assert_eq!(0xe220a8397b1dcdaf, SplitMix(0).next());
+        let date = Date::parse("2026-10-19").unwrap();
+        let (conf, map) = board(&date, GameMode::Simple);
+        assert_eq!(conf.bomb, crate::map::mine_cells(&map));
+        assert_eq!("9x9.EAgQAVAAAEACKAA", crate::board::to_code(&conf, &map));
+    }
+
+    #[test]
+    fn streaks() {
+        let today = Date::parse("2026-03-01").unwrap();
+        let records = vec![won("2026-02-26"), won("2026-02-27"), won("2026-02-28")];
+        // Today is not over yet.
+        assert_eq!(3, streak(&records, &today));
+
+        let mut records = records;
+        records.push(won("2026-03-01"));
+        assert_eq!(4, streak(&records, &today));
+
+        // A win with a hint breaks it like a loss.
+        let hinted = Record::parse("2026-02-27 hard won 100 1").unwrap();
+        let records = vec![won("2026-02-26"), hinted, won("2026-02-28")];
+        assert_eq!(1, streak(&records, &today));
+
+        let lost = Record::parse("2026-02-28 hard lost 10 0").unwrap();
+        assert_eq!(0, streak(&[won("2026-02-26"), lost], &today));
+        assert_eq!(
+            "2026-02-28 hard lost 10 0",
+            Record::parse("2026-02-28 hard lost 10 0")
+                .unwrap()
+                .to_string()
+        );
+    }
+}
@@ -1,27 +1,193 @@
 #![allow(dead_code)]
 
-use std::iter;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 use termion::{clear, color, cursor, style};
 
-use crate::{config, map};
+use crate::{config, map, Error, Result};
 
 /// The position of 2d canvas (x, y).
 #[derive(Clone)]
 pub struct Pos(pub u16, pub u16);
 
+/// One terminal cell of the back buffer: a style prefix and the text printed with it.
+#[derive(Clone, PartialEq, Eq)]
+struct Glyph {
+    style: String,
+    text: String,
+}
+
+/// The back buffer of the terminal.
+///
+/// Every draw function only writes glyphs into `back`, `present` then compares
+/// it with `front` (what the terminal shows now) and emits the changed cells
+/// in a single write.
+struct Screen {
+    front: BTreeMap<(u16, u16), Glyph>,
+    back: BTreeMap<(u16, u16), Glyph>,
+    cursor: Option<Pos>,
+    clear: bool,
+    /// Announcements waiting for the next present, only used in accessible mode.
+    lines: Vec<String>,
+}
+
+static SCREEN: Mutex<Screen> = Mutex::new(Screen {
+    front: BTreeMap::new(),
+    back: BTreeMap::new(),
+    cursor: None,
+    clear: true,
+    lines: Vec::new(),
+});
+
+/// In accessible mode nothing is painted, announcements are printed line by line instead.
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+fn screen() -> std::sync::MutexGuard<'static, Screen> {
+    SCREEN.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Put one symbol (which may carry its own colors) at (x, y).
+fn put(x: u16, y: u16, symbol: String) {
+    let glyph = Glyph {
+        style: String::new(),
+        text: symbol,
+    };
+    screen().back.insert((y, x), glyph);
+}
+
+/// Put every char of `words` from (x, y) to the right, drawn with `style`.
+fn put_str(x: u16, y: u16, words: &str, style: &str) {
+    let mut screen = screen();
+    for (i, ch) in words.chars().enumerate() {
+        let glyph = Glyph {
+            style: style.to_string(),
+            text: ch.to_string(),
+        };
+        screen.back.insert((y, x + i as u16), glyph);
+    }
+}
+
+/// Remove everything between row `from` and row `to` (both included).
+fn clear_rows(from: u16, to: u16) {
+    screen().back.retain(|&(y, _), _| y < from || y > to);
+}
+
+/// Turn accessible mode on or off.
+pub fn set_accessible(on: bool) {
+    ACCESSIBLE.store(on, Ordering::Relaxed);
+}
+
+pub fn is_accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// Queue a line of plain text for screen readers, it's ignored out of accessible mode.
+pub fn announce(words: &str) {
+    if is_accessible() {
+        screen().lines.push(words.to_string());
+    }
+}
+
 /// Clean all outputs on the screen.
 pub fn clean_output() {
-    print!("{}", clear::All)
+    let mut screen = screen();
+    screen.back.clear();
+    screen.clear = true;
 }
 
 /// Fix cursor flash every where.
 pub fn put_cursor_bottom() {
-    let (height, _) = termion::terminal_size().unwrap();
-    print!("{}", cursor::Goto(1, height));
+    screen().cursor = None;
+}
+
+/// Put the terminal cursor on a specific position after next present.
+pub fn set_cursor(pos: &Pos) {
+    screen().cursor = Some(pos.clone());
+}
+
+/// Make sure the terminal is big enough to show everything before `corner`.
+pub fn check_size(corner: &Pos) -> Result<()> {
+    if is_accessible() {
+        return Ok(());
+    }
+    let (width, height) = termion::terminal_size()
+        .map_err(|e| Error::Terminal(format!("cannot get terminal size: {}", e)))?;
+    if width < corner.0 || height < corner.1 {
+        return Err(Error::Terminal(format!(
+            "the terminal is {}x{}, but {}x{} is needed",
+            width, height, corner.0, corner.1
+        )));
+    }
+    Ok(())
+}
+
+/// Emit the difference between the last frame and the current one.
+pub fn present(out: &mut impl Write) -> io::Result<()> {
+    let mut screen = screen();
+    let screen = &mut *screen;
+    let mut frame = String::new();
+    if is_accessible() {
+        for line in screen.lines.drain(..) {
+            let _ = write!(frame, "{}\r\n", line);
+        }
+        out.write_all(frame.as_bytes())?;
+        return out.flush();
+    }
+    if screen.clear {
+        frame.push_str(clear::All.as_ref());
+        screen.front.clear();
+        screen.clear = false;
+    }
+
+    let blank = Glyph {
+        style: String::new(),
+        text: " ".to_string(),
+    };
+    let changed = screen
+        .back
+        .iter()
+        .filter(|(at, glyph)| screen.front.get(at) != Some(glyph))
+        .chain(
+            screen
+                .front
+                .keys()
+                .filter(|at| !screen.back.contains_key(at))
+                .map(|at| (at, &blank)),
+        );
+
+    let mut next_at = None;
+    let mut style = "";
+    for (&(y, x), glyph) in changed {
+        if next_at != Some((y, x)) {
+            let _ = write!(frame, "{}", cursor::Goto(x, y));
+        }
+        if glyph.style != style {
+            let _ = write!(frame, "{}{}", style::Reset, glyph.style);
+            style = &glyph.style;
+        }
+        frame.push_str(&glyph.text);
+        next_at = Some((y, x + 1));
+    }
+    if !style.is_empty() {
+        let _ = write!(frame, "{}", style::Reset);
+    }
+    screen.front.clone_from(&screen.back);
+
+    let Pos(x, y) = match &screen.cursor {
+        Some(pos) => pos.clone(),
+        None => Pos(1, termion::terminal_size().map_or(1, |(_, h)| h)),
+    };
+    let _ = write!(frame, "{}", cursor::Goto(x, y));
+
+    out.write_all(frame.as_bytes())?;
+    out.flush()
 }
 
-/// Draw a box border with positon and size, just like this:
+/// Draw a box border with positon and size, return the position below it, just like this:
 /// ```text
 /// ┌──────────┐
 /// │          │
@@ -33,18 +199,37 @@ fn draw_border(pos: &Pos, height: usize, width: usize) -> Pos {
     let Pos(x, y) = *pos;
     let (h, w) = (height as u16 + 1, width as u16 + 1);
     for i in 0..w {
-        print!("{}─", cursor::Goto(x + i, y));
-        print!("{}─", cursor::Goto(x + i, y + h));
+        put(x + i, y, "─".to_string());
+        put(x + i, y + h, "─".to_string());
     }
     for i in 0..h {
-        print!("{}│", cursor::Goto(x, y + i));
-        print!("{}│", cursor::Goto(x + w, y + i));
+        put(x, y + i, "│".to_string());
+        put(x + w, y + i, "│".to_string());
+    }
+    put(x, y, "┌".to_string());
+    put(x + w, y, "┐".to_string());
+    put(x, y + h, "└".to_string());
+    put(x + w, y + h, "┘".to_string());
+    Pos(x, y + h + 1)
+}
+
+/// Dash the sides of a border drawn by `draw_border`, to show the map wraps around:
+/// ```text
+/// ┌╌╌╌╌╌╌╌╌╌╌┐
+/// ╎          ╎
+/// └╌╌╌╌╌╌╌╌╌╌┘
+/// ```
+fn draw_wraps(pos: &Pos, height: usize, width: usize) {
+    let Pos(x, y) = *pos;
+    let (h, w) = (height as u16 + 1, width as u16 + 1);
+    for i in 1..w {
+        put(x + i, y, "╌".to_string());
+        put(x + i, y + h, "╌".to_string());
+    }
+    for i in 1..h {
+        put(x, y + i, "╎".to_string());
+        put(x + w, y + i, "╎".to_string());
     }
-    print!("{}┌", cursor::Goto(x, y));
-    print!("{}┐", cursor::Goto(x + w, y));
-    print!("{}└", cursor::Goto(x, y + h));
-    print!("{}┘", cursor::Goto(x + w, y + h));
-    Pos(x + w, y + h)
 }
 
 /// Draw a menu with given items(lenght limit: 26), just like this:
@@ -56,12 +241,16 @@ fn draw_border(pos: &Pos, height: usize, width: usize) -> Pos {
 /// │ d.four  │
 /// └─────────┘
 /// ```
-pub fn show_menu(pos: &Pos, opts: &Vec<String>, focus_idx: usize) -> Result<Pos, &'static str> {
+pub fn show_menu(pos: &Pos, opts: &[String], focus_idx: usize) -> Result<Pos> {
     if opts.len() > 26 {
-        return Err("The length of options should be between 1 and 26.");
+        return Err(Error::Terminal(
+            "The length of options should be between 1 and 26.".to_string(),
+        ));
     }
     if focus_idx >= opts.len() {
-        return Err("The focus idx should not be bigger then length of options");
+        return Err(Error::Terminal(
+            "The focus idx should not be bigger then length of options".to_string(),
+        ));
     }
     let Pos(x, y) = *pos;
     let max_height = opts.len();
@@ -71,34 +260,26 @@ pub fn show_menu(pos: &Pos, opts: &Vec<String>, focus_idx: usize) -> Result<Pos,
         .len();
     let max_width = max_word_len + 4;
     draw_border(pos, max_height, max_width);
+    announce(&format!(
+        "{}. {}, {} of {}",
+        (b'a' + focus_idx as u8) as char,
+        opts[focus_idx],
+        focus_idx + 1,
+        opts.len()
+    ));
+    let focus_style = format!("{}{}", color::Fg(color::Black), color::Bg(color::White));
     opts.iter().enumerate().for_each(|(idx, v)| {
-        let enum_char = (('a' as u8) + idx as u8) as char;
-        let space: String = iter::repeat(" ").take(max_word_len - v.len()).collect();
-        if focus_idx == idx {
-            print!(
-                "{}{}{} {}.{} {}{}",
-                color::Fg(color::Black),
-                color::Bg(color::White),
-                cursor::Goto(x + 1, y + 1 + idx as u16),
-                enum_char,
-                v,
-                space,
-                style::Reset
-            );
-        } else {
-            print!(
-                "{} {}.{} {}",
-                cursor::Goto(x + 1, y + 1 + idx as u16),
-                enum_char,
-                v,
-                space
-            );
-        }
+        let enum_char = (b'a' + idx as u8) as char;
+        let space = " ".repeat(max_word_len - v.len());
+        let line = format!(" {}.{} {}", enum_char, v, space);
+        let style = if focus_idx == idx { &focus_style } else { "" };
+        put_str(x + 1, y + 1 + idx as u16, &line, style);
     });
     put_cursor_bottom();
     Ok(Pos(x + 1 + max_width as u16, y + 1 + max_height as u16))
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShowMode {
     Normal,
     All,
@@ -106,7 +287,81 @@ pub enum ShowMode {
     Lose,
 }
 
-/// Show map, show_mode:
+/// The width of the row numbers on the left of a map.
+fn row_label_width(game_conf: &config::GameConfig) -> u16 {
+    game_conf.height.to_string().len() as u16
+}
+
+/// The height of the column letters above a map.
+fn col_label_height(game_conf: &config::GameConfig) -> u16 {
+    map::col_name(game_conf.width - 1).len() as u16
+}
+
+/// The columns inside the border of a map, a hex map takes two a cell to offset its rows.
+fn map_width(game_conf: &config::GameConfig) -> usize {
+    match game_conf.topology {
+        config::Topology::Hex => game_conf.width * 2,
+        _ => game_conf.width,
+    }
+}
+
+/// The columns from the border of a layer to the border of the next, with a gap between them.
+fn panel_width(game_conf: &config::GameConfig) -> usize {
+    map_width(game_conf) + 3
+}
+
+/// The columns inside the outer borders of all the layers, side by side.
+fn maps_width(game_conf: &config::GameConfig) -> usize {
+    (game_conf.layers - 1) * panel_width(game_conf) + map_width(game_conf)
+}
+
+/// The column of a cell inside the border, from 0.
+fn cell_col(game_conf: &config::GameConfig, row: usize, col: usize) -> usize {
+    match game_conf.topology {
+        config::Topology::Hex => col * 2 + row % 2,
+        _ => col,
+    }
+}
+
+/// The top left corner of the border of a map drawn at pos.
+fn map_origin(pos: &Pos, game_conf: &config::GameConfig) -> Pos {
+    Pos(
+        pos.0 + row_label_width(game_conf),
+        pos.1 + col_label_height(game_conf),
+    )
+}
+
+/// Draw column letters above and row numbers on the left of a map, just like this:
+/// ```text
+///   ABCDEFGHI
+///  ┌─────────┐
+/// 1│         │
+/// 2│         │
+/// ```
+/// Column names longer than one letter are stacked from top to bottom.
+/// On a hex map the letters sit over the cells of the even rows, and each layer has its own.
+fn draw_labels(pos: &Pos, game_conf: &config::GameConfig) {
+    let Pos(x, y) = map_origin(pos, game_conf);
+    for layer in 0..game_conf.layers {
+        let x = x + (layer * panel_width(game_conf)) as u16;
+        for col in 0..game_conf.width {
+            let name = map::col_name(col);
+            let top = y - name.len() as u16;
+            for (i, ch) in name.chars().enumerate() {
+                let x = x + 1 + cell_col(game_conf, 0, col) as u16;
+                put(x, top + i as u16, ch.to_string());
+            }
+        }
+    }
+    let width = row_label_width(game_conf) as usize;
+    for row in 0..game_conf.height {
+        let label = format!("{:>width$}", row + 1, width = width);
+        put_str(pos.0, y + 1 + row as u16, &label, "");
+    }
+}
+
+/// Show map with its labels, the layers side by side and numbered under their borders,
+/// return the bottom right corner of the last border, show_mode:
 /// - normal show
 /// - show all
 /// - show win
@@ -114,43 +369,214 @@ pub enum ShowMode {
 pub fn show_map(
     pos: &Pos,
     game_conf: &config::GameConfig,
-    map: &Vec<map::Cell>,
+    map: &[map::Cell],
     show_mode: ShowMode,
 ) -> Pos {
-    let Pos(x, y) = *pos;
-    let ret_pos = draw_border(&pos, game_conf.height, game_conf.width);
+    draw_labels(pos, game_conf);
+    let origin = map_origin(pos, game_conf);
+    for layer in 0..game_conf.layers {
+        let origin = Pos(origin.0 + (layer * panel_width(game_conf)) as u16, origin.1);
+        let Pos(_, bottom) = draw_border(&origin, game_conf.height, map_width(game_conf));
+        if game_conf.topology == config::Topology::Torus {
+            draw_wraps(&origin, game_conf.height, game_conf.width);
+        }
+        if game_conf.layers > 1 {
+            put_str(origin.0 + 1, bottom - 1, &(layer + 1).to_string(), "");
+        }
+    }
+    // A rule of neighbours other than the usual one is named on the top of the border.
+    let rule = format!("{}", game_conf.neighbours);
+    if game_conf.topology != config::Topology::Hex
+        && game_conf.neighbours != config::Neighbours::King
+        && rule.len() <= map_width(game_conf)
+    {
+        put_str(origin.0 + 1, origin.1, &rule, "");
+    }
     map.iter().enumerate().for_each(|(idx, cell)| {
-        let (dx, dy) = (
-            (idx % game_conf.width) as u16,
-            (idx / game_conf.width) as u16,
-        );
         let symbol = match show_mode {
             ShowMode::Normal => cell.to_string(),
             ShowMode::All => cell.get_content_symbol(),
             ShowMode::Win => match cell.content {
-                map::Content::Bomb => map::Surface::Flag.to_string(),
+                map::Content::Bomb(n) => map::Surface::Flag(n).to_string(),
                 _ => cell.get_content_symbol(),
             },
-            ShowMode::Lose => match cell.content {
-                map::Content::Bomb => cell.get_content_symbol(),
+            // Flags on mines stay, the rest show what they were wrong about.
+            ShowMode::Lose => match (&cell.surface, &cell.content) {
+                (map::Surface::Flag(_), map::Content::Bomb(_)) => cell.to_string(),
+                (map::Surface::Flag(_), _) => {
+                    format!("{}X{}", color::Fg(color::LightRed), style::Reset)
+                }
+                (map::Surface::Cover, map::Content::Bomb(1)) => "*".to_string(),
+                (map::Surface::Cover, map::Content::Bomb(n)) => map::glyph(*n).to_string(),
                 _ => cell.to_string(),
             },
         };
-        print!("{}{}", cursor::Goto(x + dx + 1, y + dy + 1), symbol);
+        let Pos(x, y) = cell_pos(pos, game_conf, idx);
+        put(x, y, symbol);
     });
-    ret_pos
+    Pos(
+        origin.0 + maps_width(game_conf) as u16 + 1,
+        origin.1 + game_conf.height as u16 + 1,
+    )
+}
+
+/// The cell index under (x, y) of a map drawn at pos, None if it's off the map.
+pub fn cell_at(pos: &Pos, game_conf: &config::GameConfig, x: u16, y: u16) -> Option<usize> {
+    let Pos(left, top) = map_origin(pos, game_conf);
+    if x <= left || y <= top {
+        return None;
+    }
+    let (dx, dy) = ((x - left - 1) as usize, (y - top - 1) as usize);
+    let (layer, dx) = (dx / panel_width(game_conf), dx % panel_width(game_conf));
+    // On a hex map the gap after a cell belongs to it.
+    let dx = match game_conf.topology {
+        config::Topology::Hex => dx.checked_sub(dy % 2)? / 2,
+        _ => dx,
+    };
+    if dx >= game_conf.width || dy >= game_conf.height || layer >= game_conf.layers {
+        return None;
+    }
+    Some(layer * game_conf.layer_size() + dy * game_conf.width + dx)
+}
+
+/// The screen position of a cell index of a map drawn at pos.
+pub fn cell_pos(pos: &Pos, game_conf: &config::GameConfig, idx: usize) -> Pos {
+    let Pos(x, y) = map_origin(pos, game_conf);
+    let (layer, idx) = (idx / game_conf.layer_size(), idx % game_conf.layer_size());
+    let (row, col) = (idx / game_conf.width, idx % game_conf.width);
+    Pos(
+        x + (layer * panel_width(game_conf) + cell_col(game_conf, row, col)) as u16 + 1,
+        y + row as u16 + 1,
+    )
+}
+
+/// Show the name and the state of the cell under cursor, below a map drawn at pos.
+pub fn show_cell_status(
+    pos: &Pos,
+    game_conf: &config::GameConfig,
+    map: &[map::Cell],
+    at: usize,
+) -> Pos {
+    let Pos(_, y) = map_origin(pos, game_conf);
+    let y = y + game_conf.height as u16 + 2;
+    let status = format!("{} {}", map::cell_name(at, game_conf), map[at].describe());
+    let width = row_label_width(game_conf) as usize + maps_width(game_conf) + 2;
+    put_str(pos.0, y, &format!("{:<width$}", status, width = width), "");
+    Pos(pos.0, y + 1)
+}
+
+/// Tint the covered cells of a map drawn at pos by their mine probabilities,
+/// from green (safe) to red (mine).
+pub fn show_heat(
+    pos: &Pos,
+    game_conf: &config::GameConfig,
+    map: &[map::Cell],
+    probs: &[Option<f64>],
+) {
+    for (idx, p) in probs.iter().enumerate() {
+        let Some(p) = p else {
+            continue;
+        };
+        if map[idx].surface != map::Surface::Cover {
+            continue;
+        }
+        let (r, g) = ((255.0 * p) as u8, (255.0 * (1.0 - p)) as u8);
+        let Pos(x, y) = cell_pos(pos, game_conf, idx);
+        let tint = color::Fg(color::Rgb(r, g, 0));
+        put(
+            x,
+            y,
+            format!("{}{}{}", tint, map::Surface::Cover, style::Reset),
+        );
+    }
+}
+
+/// Light up the cells a hint reads.
+pub fn show_highlight(
+    pos: &Pos,
+    game_conf: &config::GameConfig,
+    map: &[map::Cell],
+    cells: &[usize],
+) {
+    for &idx in cells {
+        let Pos(x, y) = cell_pos(pos, game_conf, idx);
+        let light = color::Bg(color::LightBlack);
+        put(x, y, format!("{}{}{}", light, map[idx], style::Reset));
+    }
+}
+
+/// Show the command line being edited at pos, with the cursor in it.
+pub fn show_prompt(pos: &Pos, line: &str, cursor: usize) -> Pos {
+    clear_rows(pos.1, pos.1);
+    put_str(pos.0, pos.1, &format!(":{}", line), "");
+    set_cursor(&Pos(pos.0 + 1 + cursor as u16, pos.1));
+    Pos(pos.0, pos.1 + 1)
+}
+
+/// Show lines of text from pos down, return the position under them.
+/// Chars out of ASCII take two columns, for the emoji of a shared result.
+pub fn show_text(pos: &Pos, text: &str) -> Pos {
+    let mut y = pos.1;
+    for line in text.lines() {
+        announce(line);
+        clear_rows(y, y);
+        let mut screen = screen();
+        let mut x = pos.0;
+        for ch in line.chars() {
+            let glyph = Glyph {
+                style: String::new(),
+                text: ch.to_string(),
+            };
+            screen.back.insert((y, x), glyph);
+            x += if ch.is_ascii() { 1 } else { 2 };
+        }
+        y += 1;
+    }
+    Pos(pos.0, y)
+}
+
+/// Remove everything on the line of pos.
+pub fn clear_line(pos: &Pos) {
+    clear_rows(pos.1, pos.1);
+}
+
+/// Draw the covered cells among `cells` as being pressed down.
+pub fn show_pressed(pos: &Pos, game_conf: &config::GameConfig, map: &[map::Cell], cells: &[usize]) {
+    for &idx in cells {
+        if map[idx].surface == map::Surface::Cover {
+            let Pos(x, y) = cell_pos(pos, game_conf, idx);
+            put(x, y, map::Surface::Open.to_string());
+        }
+    }
 }
 
 // Some words that said by ferris.
-const SAYS_DIFFICULTIES: [&'static str; 3] = ["Eazy as fxxk.", "It's OK.", "Really?"];
-const SAYS_END: [&'static str; 2] = ["One more time!", "Back to menu."];
+const SAYS_DIFFICULTIES: [&str; 8] = [
+    "Eazy as fxxk.",
+    "It's OK.",
+    "Really?",
+    "Same board for everyone.",
+    "No guessing allowed.",
+    "Your own board?",
+    "Got a code? Paste it anywhere here.",
+    "Make one yourself.",
+];
+const SAYS_END: [&str; 7] = [
+    "One more time!",
+    "Back to menu.",
+    "Keep it as text.",
+    "Keep it in colour.",
+    "Keep it as a picture.",
+    "Show it off!",
+    "What went wrong?",
+];
 const SAYS_QUIT: &str = "Bye~";
 const SAYS_START: &str = "Ready?";
-const SAYS_WIN: [&'static str; 2] = ["Win!", "WOW, Genius!"];
-const SAYS_LOSE: [&'static str; 2] = ["You Lose!", "BOOM!"];
-const SAYS_MINE: [&'static str; 2] = ["You're safe.", "Rua!!!"];
-const SAYS_FLAG: [&'static str; 2] = ["You can't flag here.", "Good Flag."];
-const SAYS_MOVE: [&'static str; 2] = ["Move Move Move!", "Are you sure?"];
+const SAYS_WIN: [&str; 2] = ["Win!", "WOW, Genius!"];
+const SAYS_LOSE: [&str; 2] = ["You Lose!", "BOOM!"];
+const SAYS_MINE: [&str; 2] = ["You're safe.", "Rua!!!"];
+const SAYS_FLAG: [&str; 2] = ["You can't flag here.", "Good Flag."];
+const SAYS_MOVE: [&str; 2] = ["Move Move Move!", "Are you sure?"];
 
 ///  Draw a ferris with specific str, just like this:
 ///  ```text
@@ -167,33 +593,63 @@ const SAYS_MOVE: [&'static str; 2] = ["Move Move Move!", "Are you sure?"];
 fn draw_ferris_with(pos: &Pos, words: &str, mouth: &str, leye: &str, reye: &str) -> Pos {
     let len = words.len();
     let Pos(x, y) = *pos;
-    print!("{}{}", cursor::Goto(x, y + 8), clear::BeforeCursor);
-    print!("{}{}", cursor::Goto(x, y), color::Fg(color::LightRed));
-    for i in 0..len + 2 {
-        print!("{}{}", cursor::Goto(x + 1 + i as u16, y), '_');
-    }
-    print!("{}<", cursor::Goto(x, y + 1));
-    print!(" {} >", words);
-    print!("{} ", cursor::Goto(x, y + 2));
-    for i in 0..len + 2 {
-        print!("{}{}", cursor::Goto(x + 1 + i as u16, y + 2), '-');
-    }
-    print!("{}    \\", cursor::Goto(x, y + 3));
-    print!("{}     \\", cursor::Goto(x, y + 4));
-    print!("{}    _~^~^~_", cursor::Goto(x, y + 5));
-    print!(
-        "{}\\) /  {} {}  \\ (/",
-        cursor::Goto(x, y + 6),
-        leye,
-        reye
+    let style = color::Fg(color::LightRed).to_string();
+    clear_rows(y, y + 8);
+    put_str(x + 1, y, &"_".repeat(len + 2), &style);
+    put_str(x, y + 1, &format!("< {} >", words), &style);
+    put_str(x + 1, y + 2, &"-".repeat(len + 2), &style);
+    put_str(x, y + 3, "    \\", &style);
+    put_str(x, y + 4, "     \\", &style);
+    put_str(x, y + 5, "    _~^~^~_", &style);
+    put_str(
+        x,
+        y + 6,
+        &format!("\\) /  {} {}  \\ (/", leye, reye),
+        &style,
     );
-    print!("{}  '_   {}   _'", cursor::Goto(x, y + 7), mouth);
-    print!(
-        "{}  / '-----' \\{}",
-        cursor::Goto(x, y + 8),
-        style::Reset
-    );
-    return Pos(x, y + 9);
+    put_str(x, y + 7, &format!("  '_   {}   _'", mouth), &style);
+    put_str(x, y + 8, "  / '-----' \\", &style);
+    Pos(x, y + 9)
+}
+
+pub fn ferris_says_command(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "-", "o", "o")
+}
+
+pub fn ferris_says_hint(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "o", "?", "!")
+}
+
+pub fn ferris_says_bot(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "-", "=", "=")
+}
+
+pub fn ferris_says_analysis(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "~", "o", "o")
+}
+
+pub fn ferris_says_load(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "o", "o", "o")
+}
+
+pub fn ferris_says_daily(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "v", "*", "*")
+}
+
+pub fn ferris_says_puzzle(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "-", "?", "?")
+}
+
+pub fn ferris_says_edit(pos: &Pos, words: &str) -> Pos {
+    announce(words);
+    draw_ferris_with(pos, words, "o", "-", "o")
 }
 
 pub fn ferris_says_start(pos: &Pos) -> Pos {
@@ -201,10 +657,12 @@ pub fn ferris_says_start(pos: &Pos) -> Pos {
 }
 
 pub fn ferris_says_win(pos: &Pos, idx: usize) -> Pos {
+    announce(SAYS_WIN[idx % SAYS_WIN.len()]);
     draw_ferris_with(pos, SAYS_WIN[idx % SAYS_WIN.len()], "3", "^", "^")
 }
 
 pub fn ferris_says_lose(pos: &Pos, idx: usize) -> Pos {
+    announce(SAYS_LOSE[idx % SAYS_LOSE.len()]);
     draw_ferris_with(pos, SAYS_LOSE[idx % SAYS_LOSE.len()], "x", "#", "#")
 }
 
@@ -213,7 +671,12 @@ pub fn ferris_says_mine(pos: &Pos, idx: usize) -> Pos {
 }
 
 pub fn ferris_says_flag(pos: &Pos, idx: usize) -> Pos {
-    draw_ferris_with(pos, SAYS_FLAG[idx % SAYS_FLAG.len()], "u", "o", "o")
+    let words = SAYS_FLAG[idx % SAYS_FLAG.len()];
+    // A good flag is announced with the flagged cell already.
+    if words == SAYS_FLAG[0] {
+        announce(words);
+    }
+    draw_ferris_with(pos, words, "u", "o", "o")
 }
 
 pub fn ferris_says_move(pos: &Pos, idx: usize) -> Pos {
@@ -221,6 +684,7 @@ pub fn ferris_says_move(pos: &Pos, idx: usize) -> Pos {
 }
 
 pub fn ferris_says_quit(pos: &Pos) -> Pos {
+    announce(SAYS_QUIT);
     draw_ferris_with(pos, SAYS_QUIT, "o", "-", "-")
 }
 
@@ -239,15 +703,24 @@ pub fn ferris_says_end(pos: &Pos, idx: usize) -> Pos {
 }
 
 pub fn show_bomb_status(pos: &Pos, flag_num: usize) -> Pos {
-    print!("{}{:02}", cursor::Goto(pos.0, pos.1), flag_num);
+    put_str(pos.0, pos.1, &format!("{:02}", flag_num), "");
+    Pos(pos.0, pos.1 + 1)
+}
+
+/// Show how many hints are used, a game with hints makes no record.
+pub fn show_hint_status(pos: &Pos, hints: usize) -> Pos {
+    if hints > 0 {
+        put_str(pos.0 + 3, pos.1, &format!("?{}", hints), "");
+    }
     Pos(pos.0, pos.1 + 1)
 }
 
 pub fn show_time_status(pos: &Pos, conf: &config::GameConfig, time: usize) -> Pos {
-    print!(
-        "{}{:03}",
-        cursor::Goto(pos.0 + conf.width as u16 - 1, pos.1),
-        time
+    put_str(
+        pos.0 + row_label_width(conf) + maps_width(conf) as u16 - 1,
+        pos.1,
+        &format!("{:03}", time),
+        "",
     );
     Pos(pos.0, pos.1 + 1)
 }
@@ -258,6 +731,8 @@ mod test {
     use super::draw_ferris_with;
     use super::show_menu;
     use super::Pos;
+    use super::{cell_at, cell_pos};
+    use crate::config::{GameConfig, Topology};
 
     #[test]
     fn print_border() {
@@ -283,4 +758,42 @@ mod test {
         ];
         show_menu(&Pos(1, 1), &opts, 2).unwrap();
     }
+
+    #[test]
+    fn hit_hex_cells() {
+        let conf = GameConfig {
+            topology: Topology::Hex,
+            ..GameConfig::sized(3, 4, 1)
+        };
+        let pos = Pos(1, 1);
+        for idx in 0..12 {
+            let Pos(x, y) = cell_pos(&pos, &conf, idx);
+            assert_eq!(Some(idx), cell_at(&pos, &conf, x, y));
+            // The gap after a cell belongs to it.
+            assert_eq!(Some(idx), cell_at(&pos, &conf, x + 1, y));
+        }
+        // The odd rows are offset, and start with a gap.
+        let Pos(x, y) = cell_pos(&pos, &conf, 4);
+        assert_eq!(cell_pos(&pos, &conf, 0).0 + 1, x);
+        assert_eq!(None, cell_at(&pos, &conf, x - 1, y));
+    }
+
+    #[test]
+    fn hit_layers() {
+        let conf = GameConfig {
+            layers: 2,
+            ..GameConfig::sized(2, 3, 1)
+        };
+        let pos = Pos(1, 1);
+        for idx in 0..12 {
+            let Pos(x, y) = cell_pos(&pos, &conf, idx);
+            assert_eq!(Some(idx), cell_at(&pos, &conf, x, y));
+        }
+        // The borders and the gap between the layers are off the map.
+        let Pos(x, y) = cell_pos(&pos, &conf, 2);
+        assert_eq!(x + 4, cell_pos(&pos, &conf, 6).0);
+        for x in x + 1..x + 4 {
+            assert_eq!(None, cell_at(&pos, &conf, x, y));
+        }
+    }
 }
@@ -0,0 +1,163 @@
+//! Make boards by hand: place the mines and choose the cells opened at the start,
+//! e.g. to write puzzles. The numbers follow every change.
+
+use crate::{config, map, puzzle};
+
+pub struct Editor {
+    pub conf: config::GameConfig,
+    pub map: Vec<map::Cell>,
+}
+
+impl Editor {
+    /// An empty board of the size of conf, no mines and nothing opened.
+    pub fn new(conf: &config::GameConfig) -> Editor {
+        let conf = config::GameConfig {
+            bomb: 0,
+            ..conf.clone()
+        };
+        let map = map::from_mines(&conf, &vec![false; conf.get_size()]);
+        Editor { conf, map }
+    }
+
+    /// Edit a board made before, e.g. one loaded from a file.
+    pub fn from_board(conf: &config::GameConfig, map: &[map::Cell]) -> Editor {
+        Editor {
+            conf: conf.clone(),
+            map: map.to_vec(),
+        }
+    }
+
+    fn is_mine(&self, at: usize) -> bool {
+        self.map[at].content.is_mine()
+    }
+
+    /// The mines in a cell.
+    fn mines(&self, at: usize) -> u8 {
+        match self.map[at].content {
+            map::Content::Bomb(n) => n,
+            map::Content::Number(_) => 0,
+        }
+    }
+
+    /// Put that many mines in a cell, the other cells keep theirs, a cell with mines is covered.
+    fn set_mines(&mut self, at: usize, mines: u8) {
+        let mut counts: Vec<u8> = (0..self.map.len()).map(|pos| self.mines(pos)).collect();
+        counts[at] = mines;
+        self.conf.bomb = counts.iter().map(|&n| n as usize).sum();
+        let mut map = map::from_counts(&self.conf, &counts);
+        for (cell, old) in map.iter_mut().zip(&self.map) {
+            cell.surface = old.surface.clone();
+        }
+        if mines > 0 && map[at].surface == map::Surface::Open {
+            map[at].surface = map::Surface::Cover;
+        }
+        self.map = map;
+    }
+
+    /// Put a mine on a cell or take its mines away.
+    pub fn toggle_mine(&mut self, at: usize) {
+        self.set_mines(at, if self.is_mine(at) { 0 } else { 1 });
+    }
+
+    /// One more mine in a cell, or one less when `more` is false, from none up to the stack.
+    /// Return false when the cell is already full or empty.
+    pub fn change_mines(&mut self, at: usize, more: bool) -> bool {
+        let mines = self.mines(at);
+        let mines = match more {
+            true if (mines as usize) < self.conf.stack => mines + 1,
+            false if mines > 0 => mines - 1,
+            _ => return false,
+        };
+        self.set_mines(at, mines);
+        true
+    }
+
+    /// Open a cell at the start or cover it again. Mines never start opened, return false for them.
+    pub fn toggle_open(&mut self, at: usize) -> bool {
+        if self.is_mine(at) {
+            return false;
+        }
+        self.map[at].surface = match self.map[at].surface {
+            map::Surface::Open => map::Surface::Cover,
+            _ => map::Surface::Open,
+        };
+        true
+    }
+
+    /// The cells opened at the start.
+    pub fn opened(&self) -> Vec<usize> {
+        (0..self.map.len())
+            .filter(|&pos| self.map[pos].surface == map::Surface::Open)
+            .collect()
+    }
+
+    /// What keeps the board from being played, or if it needs a guess.
+    pub fn check(&self) -> Result<bool, String> {
+        if self.conf.bomb == 0 || map::mine_cells(&self.map) == self.conf.get_size() {
+            return Err("A board needs both mines and safe cells.".to_string());
+        }
+        Ok(puzzle::solvable(&self.conf, &self.map))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Editor;
+    use crate::board;
+    use crate::config::GameConfig;
+
+    #[test]
+    fn numbers_follow_the_mines() {
+        let conf = GameConfig::sized(2, 3, 0);
+        let mut editor = Editor::new(&conf);
+        assert!(editor.check().is_err());
+        editor.toggle_mine(0);
+        assert!(editor.toggle_open(2));
+        assert!(!editor.toggle_open(0));
+        assert_eq!("*.o\n...\n", board::to_text(&editor.conf, &editor.map));
+        assert_eq!(1, editor.conf.bomb);
+
+        // A mine on an opened cell covers it.
+        editor.toggle_mine(2);
+        assert_eq!("*.*\n...\n", board::to_text(&editor.conf, &editor.map));
+        editor.toggle_mine(0);
+        editor.toggle_mine(2);
+        assert!(editor.check().is_err());
+    }
+
+    #[test]
+    fn stacked_mines() {
+        let (conf, map) = board::from_text("2.3\n...\n").unwrap();
+        let mut editor = Editor::from_board(&conf, &map);
+        editor.toggle_mine(4);
+        assert_eq!("2.3\n.*.\n", board::to_text(&editor.conf, &editor.map));
+        assert_eq!(6, editor.conf.bomb);
+        assert!(editor.change_mines(4, true));
+        assert!(editor.change_mines(4, true));
+        assert!(!editor.change_mines(4, true));
+        assert!(editor.change_mines(0, false));
+        assert_eq!("*.3\n.3.\n", board::to_text(&editor.conf, &editor.map));
+        assert_eq!(7, editor.conf.bomb);
+        editor.toggle_mine(4);
+        assert!(!editor.change_mines(4, false));
+        assert_eq!("*.3\n...\n", board::to_text(&editor.conf, &editor.map));
+
+        // As many mines as cells, yet a safe cell left.
+        let (conf, map) = board::from_text("3.\no*\n").unwrap();
+        let mut editor = Editor::from_board(&conf, &map);
+        assert_eq!(4, editor.conf.bomb);
+        assert!(editor.check().is_ok());
+        editor.toggle_mine(1);
+        editor.toggle_mine(2);
+        assert!(editor.check().is_err());
+    }
+
+    #[test]
+    fn checks_for_guesses() {
+        let (conf, map) = board::from_text("oo\n*.\n").unwrap();
+        let mut editor = Editor::from_board(&conf, &map);
+        assert_eq!(Ok(false), editor.check());
+        editor.toggle_open(3);
+        assert_eq!(Ok(true), editor.check());
+    }
+}
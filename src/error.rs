@@ -0,0 +1,45 @@
+use std::{fmt, io};
+
+/// All the errors that can happen in rua.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the input or writing the output failed.
+    Io(io::Error),
+    /// The terminal cannot show the game, e.g. it's too small.
+    Terminal(String),
+    /// A bad value in the configs.
+    Config(String),
+    /// A save file cannot be read or written.
+    Save(String),
+    /// Bad command line arguments.
+    Usage(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Terminal(msg) => write!(f, "terminal error: {}", msg),
+            Error::Config(msg) => write!(f, "config error: {}", msg),
+            Error::Save(msg) => write!(f, "save file error: {}", msg),
+            Error::Usage(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
@@ -1,356 +1,1471 @@
-use std::io::{Stdin, Stdout, Write};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use rand::rngs::ThreadRng;
-use rand::Rng;
-use termion::cursor;
+use rand::rngs::{StdRng, ThreadRng};
+use rand::{Rng, SeedableRng};
 use termion::event::{Event, Key, MouseButton, MouseEvent};
-use termion::input::{MouseTerminal, TermRead};
-use termion::raw::RawTerminal;
 use tokio::time::interval;
 
-use crate::{config, map};
+use crate::command::{self, Command, Prompt, PromptAction};
+use crate::editor::Editor;
+use crate::term::{self, Input, Terminal};
+use crate::{board, bot, config, daily, export, map, pattern, puzzle, solver, Error, Result};
 use crate::{
     config::{GameMode, GlobleConfig},
     draw::{self},
 };
 
+/// A game to play, chosen in the menu or on the command line.
+pub struct GameSetup {
+    pub conf: config::GameConfig,
+    /// A fixed board to play instead of a random one.
+    pub board: Option<Vec<map::Cell>>,
+    /// The daily challenge the board is for.
+    pub daily: Option<daily::Challenge>,
+    /// Finish the board without guessing, scored on mistakes instead of time.
+    pub puzzle: bool,
+}
+
+impl GameSetup {
+    /// A new random board of a difficulty, shaped as the config says.
+    pub fn from_game_mode(mode: GameMode, key_config: &GlobleConfig) -> GameSetup {
+        GameSetup {
+            conf: key_config.game_config(mode),
+            board: None,
+            daily: None,
+            puzzle: false,
+        }
+    }
+
+    /// Today's board of a difficulty, the first attempt of the day is ranked.
+    pub fn daily(mode: GameMode) -> Result<GameSetup> {
+        let date = daily::today();
+        let ranked = daily::start(&date, mode)?;
+        let (conf, map) = daily::board(&date, mode);
+        Ok(GameSetup {
+            conf,
+            board: Some(map),
+            daily: Some(daily::Challenge { date, mode, ranked }),
+            puzzle: false,
+        })
+    }
+
+    /// Load a board file, see `board` for the format.
+    pub fn load(path: &str) -> Result<GameSetup> {
+        let (conf, map) = board::load(path)?;
+        Ok(GameSetup {
+            conf,
+            board: Some(map),
+            daily: None,
+            puzzle: false,
+        })
+    }
+
+    /// A new puzzle of a difficulty, see `puzzle`, None when none was found.
+    pub fn puzzle(
+        mode: GameMode,
+        key_config: &GlobleConfig,
+        rng: &mut impl Rng,
+    ) -> Option<GameSetup> {
+        // Boards of layers almost never need no guess, puzzles stay flat.
+        let conf = key_config.game_config(mode);
+        let conf = config::GameConfig {
+            layers: 1,
+            bomb: conf.bomb / conf.layers,
+            ..conf
+        };
+        let map = puzzle::generate(&conf, rng)?;
+        Some(GameSetup {
+            conf,
+            board: Some(map),
+            daily: None,
+            puzzle: true,
+        })
+    }
+
+    /// Load a board file as a puzzle, it must need no guess.
+    pub fn load_puzzle(path: &str) -> Result<GameSetup> {
+        let mut setup = GameSetup::load(path)?;
+        if let Some(map) = &setup.board {
+            if !puzzle::solvable(&setup.conf, map) {
+                let msg = format!("{}: it needs a guess, so it's no puzzle", path);
+                return Err(Error::Save(msg));
+            }
+        }
+        setup.puzzle = true;
+        Ok(setup)
+    }
+
+    /// The layout of a board code, see `board`.
+    pub fn from_code(code: &str) -> Result<GameSetup> {
+        let (conf, map) = board::from_code(code)?;
+        Ok(GameSetup {
+            conf,
+            board: Some(map),
+            daily: None,
+            puzzle: false,
+        })
+    }
+}
+
 pub fn entry_event(
     init_pos: &draw::Pos,
     key_config: &GlobleConfig,
-    stdin: &Stdin,
-    stdout: &mut MouseTerminal<RawTerminal<Stdout>>,
-) -> Option<config::GameConfig> {
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<Option<GameSetup>> {
     let mut selected_item_idx = 0;
-    let difficultis = vec![GameMode::Simple, GameMode::Normal, GameMode::Hard];
-    let difficultis_items: Vec<String> = difficultis.iter().map(|v| v.to_string()).collect();
+    let difficultis = [GameMode::Simple, GameMode::Normal, GameMode::Hard];
+    let mut difficultis_items: Vec<String> = difficultis.iter().map(|v| v.to_string()).collect();
+    difficultis_items.push("Daily".to_string());
+    difficultis_items.push("Puzzle".to_string());
+    difficultis_items.push("Load".to_string());
+    difficultis_items.push("Code".to_string());
+    difficultis_items.push("Edit".to_string());
+    let daily_idx = difficultis.len();
+    let puzzle_idx = daily_idx + 1;
+    let load_idx = puzzle_idx + 1;
+    let code_idx = load_idx + 1;
+    let edit_idx = code_idx + 1;
+    // The text pasted so far, while a bracketed paste is coming in.
+    let mut pasted: Option<String> = None;
 
+    draw::announce("Choose a difficulty.");
     let pos = draw::ferris_says_difficulty(init_pos, selected_item_idx);
-    let last_pos = draw::show_menu(&pos, &difficultis_items, selected_item_idx)
-        .expect("cannot show start menu");
+    let last_pos = draw::show_menu(&pos, &difficultis_items, selected_item_idx)?;
 
-    stdout.flush().unwrap();
-    for c in stdin.lock().events() {
-        let evt = c.unwrap();
+    draw::present(stdout)?;
+    for c in input.events() {
+        let evt = c?;
+        let mut chosen = None;
         match evt {
+            Event::Unsupported(bytes) if bytes == PASTE_START => pasted = Some(String::new()),
+            Event::Unsupported(bytes) if bytes == PASTE_END => {
+                let code = pasted.take().unwrap_or_default();
+                match GameSetup::from_code(&code) {
+                    Ok(setup) => return Ok(Some(setup)),
+                    Err(err) => draw::ferris_says_load(init_pos, &err.to_string()),
+                };
+                draw::present(stdout)?;
+                continue;
+            }
+            Event::Key(Key::Char(ch)) if pasted.is_some() => {
+                pasted.get_or_insert_with(String::new).push(ch);
+                continue;
+            }
+            Event::Key(key) => match key {
+                Key::Up => selected_item_idx += difficultis_items.len() - 1,
+                Key::Down => selected_item_idx += 1,
+                Key::Char(char) => {
+                    if char == key_config.up_key {
+                        selected_item_idx += difficultis_items.len() - 1
+                    } else if char == key_config.down_key {
+                        selected_item_idx += 1
+                    } else if char == key_config.quit_key {
+                        draw::ferris_says_quit(&pos);
+                        return Ok(None);
+                    } else if char == key_config.mine_key {
+                        chosen = Some(selected_item_idx);
+                    }
+                }
+                _ => (),
+            },
+            Event::Mouse(MouseEvent::Press(btn, x, y)) => match btn {
+                MouseButton::Left => {
+                    let x = (pos.0 + 1).max((last_pos.0 - 1).min(x));
+                    let y = (pos.1 + 1).max((last_pos.1 - 1).min(y));
+                    selected_item_idx = (y - pos.1 - 1) as usize;
+                    draw::set_cursor(&draw::Pos(x, y));
+                    chosen = Some(selected_item_idx);
+                }
+                MouseButton::WheelUp => selected_item_idx += difficultis_items.len() - 1,
+                MouseButton::WheelDown => selected_item_idx += 1,
+                MouseButton::Right => {
+                    draw::ferris_says_quit(init_pos);
+                    return Ok(None);
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        selected_item_idx %= difficultis_items.len();
+        match chosen {
+            Some(idx) if idx == daily_idx => {
+                if let Some(setup) = daily_event(init_pos, &difficultis, key_config, input, stdout)?
+                {
+                    return Ok(Some(setup));
+                }
+                draw::clean_output();
+            }
+            Some(idx) if idx == puzzle_idx => {
+                if let Some(setup) =
+                    puzzle_event(init_pos, &difficultis, key_config, input, stdout)?
+                {
+                    return Ok(Some(setup));
+                }
+                draw::clean_output();
+            }
+            Some(idx) if idx == load_idx => {
+                let names = board::list();
+                if names.is_empty() {
+                    draw::ferris_says_load(init_pos, "No boards here, save one first.");
+                    draw::present(stdout)?;
+                    continue;
+                }
+                if let Some(setup) = load_event(init_pos, &names, key_config, input, stdout)? {
+                    return Ok(Some(setup));
+                }
+                draw::clean_output();
+            }
+            Some(idx) if idx == edit_idx => {
+                edit_menu_event(init_pos, &difficultis, key_config, input, stdout)?;
+                draw::clean_output();
+            }
+            Some(idx) if idx == code_idx => {
+                if let Some(setup) = code_event(init_pos, &last_pos, input, stdout)? {
+                    return Ok(Some(setup));
+                }
+                draw::clean_output();
+            }
+            Some(idx) => {
+                return Ok(Some(GameSetup::from_game_mode(
+                    difficultis[idx],
+                    key_config,
+                )))
+            }
+            None => (),
+        }
+        draw::ferris_says_difficulty(init_pos, selected_item_idx);
+        draw::show_menu(&pos, &difficultis_items, selected_item_idx)?;
+        draw::present(stdout)?;
+    }
+    Ok(None)
+}
+
+/// Choose a new puzzle of a difficulty, or one from a board file. None to go back.
+fn puzzle_event(
+    init_pos: &draw::Pos,
+    modes: &[GameMode],
+    key_config: &GlobleConfig,
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<Option<GameSetup>> {
+    draw::clean_output();
+    let names = board::list();
+    let mut items: Vec<String> = modes.iter().map(|mode| format!("New {}", mode)).collect();
+    items.extend(names.iter().cloned());
+    let pos = draw::ferris_says_puzzle(init_pos, "Which puzzle?");
+    loop {
+        let Some(idx) = menu_event(&pos, &items, key_config, input, stdout)? else {
+            return Ok(None);
+        };
+        let Some(name) = idx.checked_sub(modes.len()).map(|i| &names[i]) else {
+            match GameSetup::puzzle(modes[idx], key_config, &mut rand::thread_rng()) {
+                Some(setup) => return Ok(Some(setup)),
+                None => draw::ferris_says_puzzle(init_pos, "No puzzle found, try other rules."),
+            };
+            continue;
+        };
+        match GameSetup::load_puzzle(name) {
+            Ok(setup) => return Ok(Some(setup)),
+            Err(err) => draw::ferris_says_puzzle(init_pos, &err.to_string()),
+        };
+    }
+}
+
+/// Choose a new board of a difficulty or a board file, and edit it.
+fn edit_menu_event(
+    init_pos: &draw::Pos,
+    modes: &[GameMode],
+    key_config: &GlobleConfig,
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<()> {
+    draw::clean_output();
+    let names = board::list();
+    let mut items: Vec<String> = modes.iter().map(|mode| format!("New {}", mode)).collect();
+    items.extend(names.iter().cloned());
+    let pos = draw::ferris_says_edit(init_pos, "Which board?");
+    loop {
+        let Some(idx) = menu_event(&pos, &items, key_config, input, stdout)? else {
+            return Ok(());
+        };
+        let editor = match idx.checked_sub(modes.len()) {
+            None => Editor::new(&key_config.game_config(modes[idx])),
+            Some(i) => match board::load(&names[i]) {
+                Ok((conf, map)) => Editor::from_board(&conf, &map),
+                Err(err) => {
+                    draw::ferris_says_edit(init_pos, &err.to_string());
+                    continue;
+                }
+            },
+        };
+        return edit_event(editor, key_config, input, stdout);
+    }
+}
+
+/// The name of a key in words.
+fn key_name(key: char) -> String {
+    match key {
+        ' ' => "space".to_string(),
+        key => key.to_string(),
+    }
+}
+
+/// Edit a board by hand until the quit key: the mine key puts or takes a mine,
+/// the flag key opens a cell at the start or covers it, the hint key checks for guesses,
+/// and `:save NAME` writes the board.
+fn edit_event(
+    mut editor: Editor,
+    key_conf: &GlobleConfig,
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<()> {
+    let init_pos = draw::Pos(1, 1);
+    let help = format!(
+        "{} mine, {} opened, {} check, {}save NAME",
+        key_name(key_conf.mine_key),
+        key_name(key_conf.flag_key),
+        key_name(key_conf.hint_key),
+        key_conf.command_key
+    );
+    draw::clean_output();
+    let pos = draw::ferris_says_edit(&init_pos, &help);
+    let map_pos = draw::show_bomb_status(&pos, editor.conf.bomb);
+    let conf = editor.conf.clone();
+    let last_pos = draw::show_map(&map_pos, &conf, &editor.map, draw::ShowMode::All);
+    let mut at = 0;
+    let prompt_pos = draw::show_cell_status(&map_pos, &conf, &editor.map, at);
+    draw::check_size(&draw::Pos(last_pos.0, prompt_pos.1))?;
+    let mut prompt = Prompt::default();
+    let mut editing = false;
+    let toggle_open = |editor: &mut Editor, at: usize| {
+        if !editor.toggle_open(at) {
+            draw::ferris_says_edit(&init_pos, "Mines never start opened.");
+        }
+    };
+    let change_mines = |editor: &mut Editor, at: usize, more: bool| {
+        if !editor.change_mines(at, more) {
+            let words = format!("A cell holds 0 to {} mines.", editor.conf.stack);
+            draw::ferris_says_edit(&init_pos, &words);
+        }
+    };
+    loop {
+        draw::show_bomb_status(&pos, editor.conf.bomb);
+        draw::show_map(&map_pos, &conf, &editor.map, draw::ShowMode::All);
+        draw::show_highlight(&map_pos, &conf, &editor.map, &editor.opened());
+        draw::show_cell_status(&map_pos, &conf, &editor.map, at);
+        draw::set_cursor(&draw::cell_pos(&map_pos, &conf, at));
+        if editing {
+            draw::show_prompt(&prompt_pos, &prompt.line(), prompt.cursor());
+        } else {
+            draw::clear_line(&prompt_pos);
+        }
+        draw::present(stdout)?;
+
+        let Some(evt) = input.events().next() else {
+            return Ok(());
+        };
+        match evt? {
+            Event::Key(key) if editing => match prompt.key(key) {
+                PromptAction::Edit => (),
+                PromptAction::Cancel => editing = false,
+                PromptAction::Submit(line) => {
+                    editing = false;
+                    let words = match command::parse(&line, &conf) {
+                        Err(msg) => msg,
+                        Ok(Command::Goto(on)) => {
+                            at = on;
+                            continue;
+                        }
+                        Ok(Command::Save(name)) => match editor.check() {
+                            Err(msg) => msg,
+                            Ok(solvable) => match board::save(&name, &editor.conf, &editor.map) {
+                                Ok(path) if solvable => {
+                                    format!("Saved to {}, no guess needed.", path)
+                                }
+                                Ok(path) => format!("Saved to {}, it needs a guess.", path),
+                                Err(err) => err.to_string(),
+                            },
+                        },
+                        Ok(Command::Quit) => return Ok(()),
+                        Ok(_) => "Not in the editor.".to_string(),
+                    };
+                    draw::ferris_says_edit(&init_pos, &words);
+                }
+            },
             Event::Key(key) => {
+                let (mut dx, mut dy, mut dz) = (0, 0, 0);
                 match key {
-                    Key::Up => selected_item_idx = selected_item_idx.wrapping_sub(1),
-                    Key::Down => selected_item_idx = selected_item_idx.wrapping_add(1),
+                    Key::Up => dy = -1,
+                    Key::Down => dy = 1,
+                    Key::Left => dx = -1,
+                    Key::Right => dx = 1,
+                    Key::PageUp => dz = -1,
+                    Key::PageDown => dz = 1,
                     Key::Char(char) => {
-                        if char == key_config.up_key {
-                            selected_item_idx = selected_item_idx.wrapping_sub(1)
-                        } else if char == key_config.down_key {
-                            selected_item_idx = selected_item_idx.wrapping_add(1)
-                        } else if char == key_config.quit_key {
-                            draw::ferris_says_quit(&pos);
-                            return None;
-                        } else if char == key_config.mine_key {
-                            let game_mode = config::GameMode::from_usize(selected_item_idx);
-                            let game_config = config::GameConfig::from_game_mode(game_mode);
-                            return Some(game_config);
+                        if char == key_conf.up_key {
+                            dy = -1
+                        } else if char == key_conf.down_key {
+                            dy = 1
+                        } else if char == key_conf.left_key {
+                            dx = -1
+                        } else if char == key_conf.right_key {
+                            dx = 1
+                        } else if char == key_conf.prev_layer_key {
+                            dz = -1
+                        } else if char == key_conf.next_layer_key {
+                            dz = 1
+                        } else if char == key_conf.mine_key {
+                            editor.toggle_mine(at);
+                        } else if char == key_conf.faster_key || char == key_conf.slower_key {
+                            change_mines(&mut editor, at, char == key_conf.faster_key);
+                        } else if char == key_conf.flag_key {
+                            toggle_open(&mut editor, at);
+                        } else if char == key_conf.hint_key {
+                            let words = match editor.check() {
+                                Err(msg) => msg,
+                                Ok(true) => "No guess needed.".to_string(),
+                                Ok(false) => "It needs a guess.".to_string(),
+                            };
+                            draw::ferris_says_edit(&init_pos, &words);
+                        } else if char == key_conf.command_key {
+                            editing = true;
+                            prompt.start();
+                            draw::announce("Command:");
+                        } else if char == key_conf.quit_key {
+                            return Ok(());
                         }
                     }
                     _ => (),
                 }
-                selected_item_idx %= difficultis_items.len();
-                draw::ferris_says_difficulty(init_pos, selected_item_idx);
-                draw::show_menu(&pos, &difficultis_items, selected_item_idx)
-                    .expect("cannot show start menu");
+                if (dx, dy, dz) != (0, 0, 0) {
+                    at = map::step_layer(&conf, map::step(&conf, at, dx, dy), dz);
+                }
+                draw::announce(&map::describe_at(&conf, &editor.map, at));
             }
             Event::Mouse(MouseEvent::Press(btn, x, y)) => {
+                let Some(on) = draw::cell_at(&map_pos, &conf, x, y) else {
+                    continue;
+                };
+                at = on;
                 match btn {
-                    MouseButton::Left => {
-                        let x = (pos.0 + 1).max((last_pos.0 - 1).min(x));
-                        let y = (pos.1 + 1).max((last_pos.1 - 1).min(y));
-                        let selected_item_idx = (y - pos.1 - 1) as usize;
+                    MouseButton::Left => editor.toggle_mine(at),
+                    MouseButton::Right => toggle_open(&mut editor, at),
+                    MouseButton::WheelUp => change_mines(&mut editor, at, true),
+                    MouseButton::WheelDown => change_mines(&mut editor, at, false),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+}
 
-                        let pos = draw::ferris_says_difficulty(init_pos, selected_item_idx);
-                        draw::show_menu(&pos, &difficultis_items, selected_item_idx)
-                            .expect("cannot show start menu");
-                        print!("{}", cursor::Goto(x, y));
+/// What a terminal sends around a bracketed paste.
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
 
-                        let game_mode = config::GameMode::from_usize(selected_item_idx);
-                        let game_config = config::GameConfig::from_game_mode(game_mode);
-                        return Some(game_config);
+/// Type or paste a board code on the line under pos, None to go back.
+fn code_event(
+    init_pos: &draw::Pos,
+    pos: &draw::Pos,
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<Option<GameSetup>> {
+    let prompt_pos = draw::Pos(1, pos.1 + 1);
+    let mut prompt = Prompt::default();
+    prompt.start();
+    draw::ferris_says_load(init_pos, "Type the code.");
+    draw::show_prompt(&prompt_pos, &prompt.line(), prompt.cursor());
+    draw::present(stdout)?;
+    for c in input.events() {
+        // The marks of a paste are left out, its chars are typed.
+        if let Event::Key(key) = c? {
+            match prompt.key(key) {
+                PromptAction::Edit => (),
+                PromptAction::Cancel => return Ok(None),
+                PromptAction::Submit(code) => match GameSetup::from_code(&code) {
+                    Ok(setup) => return Ok(Some(setup)),
+                    Err(err) => {
+                        draw::ferris_says_load(init_pos, &err.to_string());
+                        prompt.start();
                     }
-                    MouseButton::WheelUp => selected_item_idx = selected_item_idx.wrapping_sub(1),
-                    MouseButton::WheelDown => selected_item_idx = selected_item_idx.wrapping_add(1),
-                    MouseButton::Right => {
-                        draw::ferris_says_quit(init_pos);
-                        return None;
+                },
+            }
+        }
+        draw::show_prompt(&prompt_pos, &prompt.line(), prompt.cursor());
+        draw::present(stdout)?;
+    }
+    Ok(None)
+}
+
+/// Choose a board file among names, None to go back.
+fn load_event(
+    init_pos: &draw::Pos,
+    names: &[String],
+    key_config: &GlobleConfig,
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<Option<GameSetup>> {
+    draw::clean_output();
+    let pos = draw::ferris_says_load(init_pos, "Which board?");
+    loop {
+        let Some(idx) = menu_event(&pos, names, key_config, input, stdout)? else {
+            return Ok(None);
+        };
+        match GameSetup::load(&names[idx]) {
+            Ok(setup) => return Ok(Some(setup)),
+            Err(err) => draw::ferris_says_load(init_pos, &err.to_string()),
+        };
+    }
+}
+
+/// Choose the difficulty of today's board, None to go back.
+fn daily_event(
+    init_pos: &draw::Pos,
+    modes: &[GameMode],
+    key_config: &GlobleConfig,
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<Option<GameSetup>> {
+    draw::clean_output();
+    let date = daily::today();
+    let records = daily::history().unwrap_or_default();
+    // Show what is played already today.
+    let items: Vec<String> = modes
+        .iter()
+        .map(|&mode| match daily::played(&records, &date, mode) {
+            Some(record) => format!("{} ({} {}s)", mode, record.outcome, record.secs),
+            None => mode.to_string(),
+        })
+        .collect();
+    let words = format!(
+        "{}, {} days in a row.",
+        date,
+        daily::streak(&records, &date)
+    );
+    let pos = draw::ferris_says_daily(init_pos, &words);
+    loop {
+        let Some(idx) = menu_event(&pos, &items, key_config, input, stdout)? else {
+            return Ok(None);
+        };
+        match GameSetup::daily(modes[idx]) {
+            Ok(setup) => return Ok(Some(setup)),
+            Err(err) => draw::ferris_says_daily(init_pos, &err.to_string()),
+        };
+    }
+}
+
+/// Let the player pick one of the items, in a menu drawn at pos.
+/// Return its index, None if the player goes back.
+fn menu_event(
+    pos: &draw::Pos,
+    items: &[String],
+    key_config: &GlobleConfig,
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<Option<usize>> {
+    let mut selected_item_idx = 0;
+    let last_pos = draw::show_menu(pos, items, selected_item_idx)?;
+    draw::present(stdout)?;
+    for c in input.events() {
+        match c? {
+            Event::Key(key) => match key {
+                Key::Up => selected_item_idx += items.len() - 1,
+                Key::Down => selected_item_idx += 1,
+                Key::Char(char) => {
+                    if char == key_config.up_key {
+                        selected_item_idx += items.len() - 1
+                    } else if char == key_config.down_key {
+                        selected_item_idx += 1
+                    } else if char == key_config.quit_key {
+                        return Ok(None);
+                    } else if char == key_config.mine_key {
+                        return Ok(Some(selected_item_idx));
                     }
-                    _ => (),
                 }
-                selected_item_idx %= difficultis_items.len();
-                draw::ferris_says_difficulty(init_pos, selected_item_idx);
-                draw::show_menu(&pos, &difficultis_items, selected_item_idx)
-                    .expect("cannot show start menu");
-            }
+                _ => (),
+            },
+            Event::Mouse(MouseEvent::Press(btn, _, y)) => match btn {
+                MouseButton::Left => {
+                    let y = (pos.1 + 1).max((last_pos.1 - 1).min(y));
+                    return Ok(Some((y - pos.1 - 1) as usize));
+                }
+                MouseButton::WheelUp => selected_item_idx += items.len() - 1,
+                MouseButton::WheelDown => selected_item_idx += 1,
+                MouseButton::Right => return Ok(None),
+                _ => (),
+            },
             _ => (),
         }
-        stdout.flush().unwrap();
+        selected_item_idx %= items.len();
+        draw::show_menu(pos, items, selected_item_idx)?;
+        draw::present(stdout)?;
+    }
+    Ok(None)
+}
+
+/// The bounds of the delay between two moves of the bot.
+const MIN_DELAY: Duration = Duration::from_millis(10);
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Everything that changes while playing one game.
+struct GameState {
+    map: Vec<map::Cell>,
+    init_mine: bool,
+    flag_num: usize,
+    left_cover: usize,
+    /// Hints asked for, a game with any makes no record.
+    hints: usize,
+    /// What went wrong, once the game is lost.
+    analysis: Option<Analysis>,
+    board_rng: StdRng,
+    started: Instant,
+    /// The mistakes so far, in a puzzle.
+    puzzle: Option<Mistakes>,
+}
+
+/// What a puzzle is scored on.
+#[derive(Default)]
+struct Mistakes {
+    /// Opens of cells nothing proved safe.
+    guesses: usize,
+    /// Flags on cells nothing proved a mine.
+    flags: usize,
+}
+
+impl Mistakes {
+    fn score(&self) -> String {
+        match (self.guesses, self.flags) {
+            (0, 0) => "Solved, no mistakes!".to_string(),
+            (guesses, flags) => {
+                format!("Solved, {} guesses and {} unproved flags.", guesses, flags)
+            }
+        }
+    }
+}
+
+/// What went wrong in a lost game, shown by the end menu.
+pub struct Analysis {
+    pub words: String,
+    /// Where a better cell to open is on the screen.
+    pub better: Option<draw::Pos>,
+}
+
+/// A game played to its end.
+pub struct Finished {
+    /// The end menu goes next to the map ending here.
+    pub pos: draw::Pos,
+    pub map: Vec<map::Cell>,
+    /// How the map is shown now: won, lost, or left as it is.
+    pub mode: draw::ShowMode,
+    /// What went wrong, for a lost game.
+    pub analysis: Option<Analysis>,
+    /// Seconds from the start to the end.
+    pub secs: u64,
+    pub hints: usize,
+}
+
+/// How a game ends.
+pub enum GameEnd {
+    /// Won, lost or left by the quit key.
+    Over(Finished),
+    /// Start another game at once.
+    Restart,
+    /// Leave the whole program.
+    Quit,
+}
+
+impl GameState {
+    /// Wrap the game up, the end menu goes next to the map ending at pos.
+    fn finish(&mut self, pos: draw::Pos) -> Finished {
+        let mode = if map::is_exploded(&self.map) {
+            draw::ShowMode::Lose
+        } else if self.left_cover == map::mine_cells(&self.map) {
+            draw::ShowMode::Win
+        } else {
+            draw::ShowMode::Normal
+        };
+        Finished {
+            pos,
+            map: std::mem::take(&mut self.map),
+            mode,
+            analysis: self.analysis.take(),
+            secs: self.started.elapsed().as_secs(),
+            hints: self.hints,
+        }
+    }
+}
+
+/// The mouse buttons held down on the map, and the cell under them.
+#[derive(Default)]
+struct Press {
+    left: bool,
+    right: bool,
+    chord: bool,
+    at: Option<usize>,
+}
+
+impl Press {
+    fn is_held(&self) -> bool {
+        self.left || self.right || self.chord
+    }
+
+    fn is_chord(&self) -> bool {
+        self.chord || (self.left && self.right)
+    }
+
+    /// The cells drawn pressed down, a 3x3 block for chords.
+    fn cells(&self, conf: &config::GameConfig) -> Vec<usize> {
+        let mut cells = vec![];
+        if let Some(at) = self.at {
+            cells.push(at);
+            if self.is_chord() {
+                map::check_around_fn(conf, at, |pos| cells.push(pos));
+            }
+        }
+        cells
     }
-    None
 }
 
 #[tokio::main]
 pub async fn game_event(
     key_conf: &config::GlobleConfig,
-    game_conf: &config::GameConfig,
-    stdin: &Stdin,
-    stdout: &mut MouseTerminal<RawTerminal<Stdout>>,
+    setup: &GameSetup,
+    input: &Input,
+    stdout: &mut Terminal,
     rng: &mut ThreadRng,
-) -> draw::Pos {
-    let mut init_mine = true;
-
-    let mut flag_num = 0;
-    let mut left_cover = game_conf.get_size();
-    // init map
-    let mut graph_map =
-        vec![map::Cell::new(map::Content::Zero, map::Surface::Cover); game_conf.get_size()];
+    prompt: &mut Prompt,
+) -> Result<GameEnd> {
+    let game_conf = &setup.conf;
+    let mut state = GameState {
+        map: vec![
+            map::Cell::new(map::Content::Number(0), map::Surface::Cover);
+            game_conf.get_size()
+        ],
+        init_mine: true,
+        flag_num: 0,
+        left_cover: game_conf.get_size(),
+        hints: 0,
+        analysis: None,
+        board_rng: StdRng::seed_from_u64(rng.gen()),
+        started: Instant::now(),
+        puzzle: setup.puzzle.then(Mistakes::default),
+    };
+    if let Some(board) = &setup.board {
+        state.map = board.clone();
+        state.init_mine = false;
+        state.flag_num = board.iter().map(|c| c.surface.flags()).sum();
+        state.left_cover = board
+            .iter()
+            .filter(|c| c.surface != map::Surface::Open)
+            .count();
+    }
+    let mut press = Press::default();
+    let mut editing = false;
+    // The heatmap is computed again only after some cells are opened.
+    let mut heat: Option<(usize, Vec<Option<f64>>)> = None;
+    let mut show_heat = false;
+    let mut autoplay = false;
+    let mut delay = key_conf.auto_delay;
+
     let init_pos = draw::Pos(1, 1);
-    let pos = draw::ferris_says_start(&init_pos);
+    let pos = match &setup.daily {
+        _ if setup.puzzle => draw::ferris_says_puzzle(&init_pos, "Finish it, no guessing."),
+        Some(challenge) if challenge.ranked => {
+            draw::ferris_says_daily(&init_pos, "Ranked, good luck!")
+        }
+        Some(_) => draw::ferris_says_daily(&init_pos, "Played today, just for fun."),
+        None => draw::ferris_says_start(&init_pos),
+    };
 
-    let interval_handle = tokio::spawn(time_record_event(pos.clone(), game_conf.clone()));
-    let pos = draw::show_bomb_status(&pos, game_conf.bomb - flag_num);
+    // Puzzles are not played against the clock.
+    let interval_handle =
+        (!setup.puzzle).then(|| tokio::spawn(time_record_event(pos.clone(), game_conf.clone())));
+    let pos = draw::show_bomb_status(&pos, game_conf.bomb - state.flag_num);
 
-    let last_pos = draw::show_map(&pos, game_conf, &graph_map, draw::ShowMode::Normal);
-    let init_at = draw::Pos(pos.0 + 1, pos.1 + 1);
-    let mut now_at = draw::Pos(pos.0 + 1, pos.1 + 1);
-    print!("{}", cursor::Goto(now_at.0, now_at.1));
+    let last_pos = draw::show_map(&pos, game_conf, &state.map, draw::ShowMode::Normal);
+    let mut at = 0;
+    let prompt_pos = draw::show_cell_status(&pos, game_conf, &state.map, at);
+    draw::check_size(&draw::Pos(last_pos.0, prompt_pos.1))?;
+    draw::set_cursor(&draw::cell_pos(&pos, game_conf, at));
+    let layers = match game_conf.layers {
+        1 => String::new(),
+        n => format!(", {} layers", n),
+    };
+    draw::announce(&format!(
+        "{} columns, {} rows{}, {} mines. Press {} for commands.",
+        game_conf.width, game_conf.height, layers, game_conf.bomb, key_conf.command_key
+    ));
+    draw::announce(&map::describe_at(game_conf, &state.map, at));
 
-    stdout.lock().flush().unwrap();
-    for c in stdin.lock().events() {
-        let evt = c.unwrap();
+    draw::present(stdout)?;
+    loop {
+        // The bot moves when no event comes in time.
+        let evt = if autoplay {
+            input.wait(delay)?
+        } else {
+            match input.events().next() {
+                Some(evt) => Some(evt?),
+                None => break,
+            }
+        };
         match evt {
-            Event::Key(key) => {
+            None => match bot_event(&init_pos, &pos, game_conf, rng, &mut state, &mut at) {
+                Some(true) => return Ok(GameEnd::Over(state.finish(last_pos))),
+                Some(false) => (),
+                None => autoplay = false,
+            },
+            Some(Event::Key(key)) if editing => match prompt.key(key) {
+                PromptAction::Edit => (),
+                PromptAction::Cancel => editing = false,
+                PromptAction::Submit(line) => {
+                    editing = false;
+                    draw::clear_line(&prompt_pos);
+                    let cmd = command::parse(&line, game_conf);
+                    match cmd {
+                        Err(msg) => {
+                            draw::ferris_says_command(&init_pos, &msg);
+                        }
+                        Ok(Command::Open(on)) | Ok(Command::Chord(on)) => {
+                            at = on;
+                            let chord = matches!(cmd, Ok(Command::Chord(_)));
+                            if mine_event(&init_pos, &pos, game_conf, rng, &mut state, at, chord) {
+                                return Ok(GameEnd::Over(state.finish(last_pos)));
+                            }
+                        }
+                        Ok(Command::Flag(on)) => {
+                            at = on;
+                            flag_event(&init_pos, game_conf, &mut state, at);
+                        }
+                        Ok(Command::Goto(on)) => {
+                            at = on;
+                            draw::announce(&map::describe_at(game_conf, &state.map, at));
+                        }
+                        Ok(Command::Hint) => {
+                            at = hint_event(&init_pos, &pos, game_conf, &mut state, at);
+                        }
+                        Ok(Command::Save(name)) => {
+                            let words = if state.init_mine {
+                                "Nothing to save yet.".to_string()
+                            } else {
+                                match board::save(&name, game_conf, &state.map) {
+                                    Ok(path) => format!("Saved to {}.", path),
+                                    Err(err) => err.to_string(),
+                                }
+                            };
+                            draw::ferris_says_command(&init_pos, &words);
+                        }
+                        Ok(Command::Code) => {
+                            // The mines go down with the first open.
+                            let words = if state.init_mine {
+                                "No mines yet, open a cell first.".to_string()
+                            } else {
+                                format!("Code: {}", board::to_code(game_conf, &state.map))
+                            };
+                            draw::ferris_says_command(&init_pos, &words);
+                        }
+                        Ok(Command::Restart) => return Ok(GameEnd::Restart),
+                        Ok(Command::Quit) => {
+                            draw::ferris_says_quit(&init_pos);
+                            return Ok(GameEnd::Quit);
+                        }
+                    }
+                }
+            },
+            Some(Event::Key(key)) => {
                 draw::ferris_says_move(&init_pos, rng.gen_range(0..2));
+                let (mut dx, mut dy, mut dz) = (0, 0, 0);
                 match key {
-                    Key::Up => now_at.1 -= 1,
-                    Key::Down => now_at.1 += 1,
-                    Key::Left => now_at.0 -= 1,
-                    Key::Right => now_at.0 += 1,
+                    Key::Up => dy = -1,
+                    Key::Down => dy = 1,
+                    Key::Left => dx = -1,
+                    Key::Right => dx = 1,
+                    Key::PageUp => dz = -1,
+                    Key::PageDown => dz = 1,
                     Key::Char(char) => {
                         if char == key_conf.up_key {
-                            now_at.1 -= 1
+                            dy = -1
                         } else if char == key_conf.down_key {
-                            now_at.1 += 1
+                            dy = 1
                         } else if char == key_conf.left_key {
-                            now_at.0 -= 1
+                            dx = -1
                         } else if char == key_conf.right_key {
-                            now_at.0 += 1
+                            dx = 1
+                        } else if char == key_conf.prev_layer_key {
+                            dz = -1
+                        } else if char == key_conf.next_layer_key {
+                            dz = 1
                         } else if char == key_conf.mine_key {
-                            match mine_event(
-                                &init_pos,
-                                &pos,
-                                &init_at,
-                                &now_at,
-                                game_conf,
-                                rng,
-                                &mut init_mine,
-                                &mut left_cover,
-                                graph_map,
-                            ) {
-                                Some(res) => graph_map = res,
-                                None => return last_pos,
+                            if mine_event(&init_pos, &pos, game_conf, rng, &mut state, at, false) {
+                                return Ok(GameEnd::Over(state.finish(last_pos)));
                             }
                         } else if char == key_conf.flag_key {
-                            graph_map = flag_event(
-                                &init_pos,
-                                &init_at,
-                                &now_at,
-                                game_conf,
-                                init_mine,
-                                graph_map,
-                                &mut flag_num,
-                            );
+                            flag_event(&init_pos, game_conf, &mut state, at);
+                        } else if char == key_conf.hint_key {
+                            at = hint_event(&init_pos, &pos, game_conf, &mut state, at);
+                        } else if char == key_conf.auto_key {
+                            autoplay = !autoplay;
+                            let words = if autoplay {
+                                "Let me play."
+                            } else {
+                                "Your turn."
+                            };
+                            draw::ferris_says_bot(&init_pos, words);
+                        } else if char == key_conf.step_key {
+                            autoplay = false;
+                            let over =
+                                bot_event(&init_pos, &pos, game_conf, rng, &mut state, &mut at);
+                            if over == Some(true) {
+                                return Ok(GameEnd::Over(state.finish(last_pos)));
+                            }
+                        } else if char == key_conf.faster_key || char == key_conf.slower_key {
+                            delay = if char == key_conf.faster_key {
+                                (delay / 2).max(MIN_DELAY)
+                            } else {
+                                (delay * 2).min(MAX_DELAY)
+                            };
+                            let words = format!("One move every {}ms.", delay.as_millis());
+                            draw::ferris_says_bot(&init_pos, &words);
+                        } else if char == key_conf.heat_key {
+                            show_heat = !show_heat;
+                            draw::show_map(&pos, game_conf, &state.map, draw::ShowMode::Normal);
+                        } else if char == key_conf.command_key {
+                            editing = true;
+                            prompt.start();
+                            draw::announce("Command:");
                         } else if char == key_conf.quit_key {
                             draw::ferris_says_quit(&init_pos);
-                            return last_pos;
+                            return Ok(GameEnd::Over(state.finish(last_pos)));
                         }
                     }
                     _ => (),
                 }
-                let x = (pos.0 + 1).max((last_pos.0 - 1).min(now_at.0));
-                let y = (pos.1 + 1).max((last_pos.1 - 1).min(now_at.1));
-                now_at = draw::Pos(x, y);
-                print!("{}", cursor::Goto(x, y));
+                if (dx, dy, dz) != (0, 0, 0) {
+                    at = map::step_layer(game_conf, map::step(game_conf, at, dx, dy), dz);
+                    draw::announce(&map::describe_at(game_conf, &state.map, at));
+                }
             }
-            Event::Mouse(MouseEvent::Press(btn, x, y)) => {
-                let x = (pos.0 + 1).max((last_pos.0 - 1).min(x));
-                let y = (pos.1 + 1).max((last_pos.1 - 1).min(y));
-                now_at = draw::Pos(x, y);
-
+            Some(Event::Mouse(MouseEvent::Press(btn, x, y))) => {
+                let on = draw::cell_at(&pos, game_conf, x, y);
+                // A press starts on the map, or joins a press already held.
+                if on.is_none() && !press.is_held() {
+                    continue;
+                }
                 match btn {
-                    MouseButton::Left => match mine_event(
-                        &init_pos,
-                        &pos,
-                        &init_at,
-                        &now_at,
-                        game_conf,
-                        rng,
-                        &mut init_mine,
-                        &mut left_cover,
-                        graph_map,
-                    ) {
-                        Some(res) => graph_map = res,
-                        None => return last_pos,
-                    },
-                    MouseButton::Right => {
-                        graph_map = flag_event(
-                            &init_pos,
-                            &init_at,
-                            &now_at,
-                            game_conf,
-                            init_mine,
-                            graph_map,
-                            &mut flag_num,
-                        );
+                    MouseButton::Left => press.left = true,
+                    MouseButton::Right => press.right = true,
+                    MouseButton::Middle => press.chord = true,
+                    _ => continue,
+                }
+                press.at = on;
+                show_press(&pos, game_conf, &state, &press, &mut at);
+            }
+            Some(Event::Mouse(MouseEvent::Hold(x, y))) => {
+                if !press.is_held() {
+                    continue;
+                }
+                press.at = draw::cell_at(&pos, game_conf, x, y);
+                show_press(&pos, game_conf, &state, &press, &mut at);
+            }
+            Some(Event::Mouse(MouseEvent::Release(x, y))) => {
+                let press = std::mem::take(&mut press);
+                // Released off the map cancels the action.
+                if let Some(on) = draw::cell_at(&pos, game_conf, x, y) {
+                    at = on;
+                    if press.is_chord() || press.left {
+                        let chord = press.is_chord();
+                        if mine_event(&init_pos, &pos, game_conf, rng, &mut state, at, chord) {
+                            return Ok(GameEnd::Over(state.finish(last_pos)));
+                        }
+                    } else if press.right {
+                        flag_event(&init_pos, game_conf, &mut state, at);
                     }
-                    _ => (),
                 }
-                print!("{}", cursor::Goto(now_at.0, now_at.1));
+                draw::show_map(&pos, game_conf, &state.map, draw::ShowMode::Normal);
             }
             _ => (),
         }
-        stdout.lock().flush().unwrap();
+        if show_heat {
+            if heat.as_ref().map(|(left, _)| *left) != Some(state.left_cover) {
+                let probs = solver::probabilities(game_conf, &state.map);
+                heat = Some((state.left_cover, probs));
+            }
+            if let Some((_, probs)) = &heat {
+                draw::show_heat(&pos, game_conf, &state.map, probs);
+            }
+            draw::show_pressed(&pos, game_conf, &state.map, &press.cells(game_conf));
+        }
+        draw::show_cell_status(&pos, game_conf, &state.map, at);
+        draw::set_cursor(&draw::cell_pos(&pos, game_conf, at));
+        if editing {
+            draw::show_prompt(&prompt_pos, &prompt.line(), prompt.cursor());
+        } else {
+            draw::clear_line(&prompt_pos);
+        }
+        draw::present(stdout)?;
+    }
+    if let Some(handle) = interval_handle {
+        let _ = handle.await;
+    }
+    Ok(GameEnd::Over(state.finish(last_pos)))
+}
+
+/// Draw the cells under a held press, and follow it with the cursor.
+fn show_press(
+    map_pos: &draw::Pos,
+    conf: &config::GameConfig,
+    state: &GameState,
+    press: &Press,
+    at: &mut usize,
+) {
+    draw::show_map(map_pos, conf, &state.map, draw::ShowMode::Normal);
+    draw::show_pressed(map_pos, conf, &state.map, &press.cells(conf));
+    if let Some(on) = press.at {
+        *at = on;
     }
-    match tokio::join!(interval_handle) {
-        _ => (),
-    };
-    last_pos
 }
 
+/// Open a cell (or chord it), return true if the game is over.
 fn mine_event(
     init_pos: &draw::Pos,
     map_pos: &draw::Pos,
-    init_at: &draw::Pos,
-    now_at: &draw::Pos,
     conf: &config::GameConfig,
     rng: &mut ThreadRng,
-    init_mine: &mut bool,
-    left_cover: &mut usize,
-    mut graph_map: Vec<map::Cell>,
-) -> Option<Vec<map::Cell>> {
+    state: &mut GameState,
+    at: usize,
+    chord: bool,
+) -> bool {
     draw::ferris_says_mine(init_pos, rng.gen_range(0..2));
-    if *init_mine {
-        *init_mine = false;
-        graph_map = map::gen_map(init_at, now_at, conf, rng);
-    }
-    graph_map = map::mine_map(init_at, now_at, conf, graph_map, left_cover);
-    let pos = (now_at.1 - init_at.1) as usize * conf.width + (now_at.0 - init_at.0) as usize;
-    if let (map::Surface::Open, map::Content::Bomb) =
-        (&graph_map[pos].surface, &graph_map[pos].content)
-    {
-        draw::show_map(map_pos, conf, &graph_map, draw::ShowMode::Lose);
+    if state.init_mine {
+        if chord {
+            return false;
+        }
+        state.init_mine = false;
+        state.map = map::gen_map(at, conf, &mut state.board_rng);
+    }
+    let before = state.map.clone();
+    let graph_map = std::mem::take(&mut state.map);
+    state.map = if chord || graph_map[at].surface == map::Surface::Open {
+        map::chord_map(at, conf, graph_map, &mut state.left_cover)
+    } else {
+        map::mine_map(at, conf, graph_map, &mut state.left_cover)
+    };
+    let mut guessed = false;
+    if let Some(mistakes) = &mut state.puzzle {
+        // The cells chosen are the one clicked, or those around it for a chord.
+        let mut chosen = vec![at];
+        if chord || before[at].surface == map::Surface::Open {
+            map::check_around_fn(conf, at, |pos| chosen.push(pos));
+        }
+        let probs = solver::probabilities(conf, &before);
+        guessed = chosen.iter().any(|&pos| {
+            before[pos].surface == map::Surface::Cover
+                && state.map[pos].surface == map::Surface::Open
+                && !probs[pos].is_some_and(solver::is_safe)
+        });
+        mistakes.guesses += guessed as usize;
+    }
+    if !map::is_exploded(&state.map) {
+        draw::announce(&map::describe_opened(&before, &state.map));
+    }
+    if map::is_exploded(&state.map) {
+        draw::announce(&format!("{} was a mine.", map::cell_name(at, conf)));
+        state.analysis = Some(analyse(map_pos, conf, &before, &state.map));
+        draw::show_map(map_pos, conf, &state.map, draw::ShowMode::Lose);
         draw::ferris_says_lose(init_pos, rng.gen_range(0..2));
-        return None;
+        return true;
     }
-    if *left_cover == conf.bomb {
-        let pos = draw::ferris_says_win(init_pos, rng.gen_range(0..2));
+    if state.left_cover == map::mine_cells(&state.map) {
+        let pos = match &state.puzzle {
+            Some(mistakes) => draw::ferris_says_puzzle(init_pos, &mistakes.score()),
+            None => draw::ferris_says_win(init_pos, rng.gen_range(0..2)),
+        };
         draw::show_bomb_status(&pos, 0);
-        draw::show_map(map_pos, conf, &graph_map, draw::ShowMode::Win);
-        return None;
+        draw::show_map(map_pos, conf, &state.map, draw::ShowMode::Win);
+        return true;
+    }
+    if guessed {
+        draw::ferris_says_puzzle(init_pos, "That was a guess.");
+    }
+    draw::show_map(map_pos, conf, &state.map, draw::ShowMode::Normal);
+    false
+}
+
+fn flag_event(init_pos: &draw::Pos, conf: &config::GameConfig, state: &mut GameState, at: usize) {
+    if state.init_mine {
+        draw::ferris_says_flag(init_pos, 0);
+        return;
     }
-    draw::show_map(map_pos, conf, &graph_map, draw::ShowMode::Normal);
-    Some(graph_map)
+    let graph_map = std::mem::take(&mut state.map);
+    let (graph_map, ok) = map::flag_map(at, conf, graph_map, &mut state.flag_num);
+    state.map = graph_map;
+    // A flag put on a covered cell, not one more on a flagged one.
+    let flagged = ok && state.map[at].surface == map::Surface::Flag(1);
+    if ok {
+        let (action, times) = match state.map[at].surface {
+            map::Surface::Flag(1) => ("Flagged", String::new()),
+            map::Surface::Flag(n) => ("Flagged", format!(" {} times", n)),
+            _ => ("Unflagged", String::new()),
+        };
+        let name = map::cell_name(at, conf);
+        let left = conf.bomb - state.flag_num;
+        draw::announce(&format!(
+            "{} {}{}, {} mines left.",
+            action, name, times, left
+        ));
+    }
+    let mut pos = draw::ferris_says_flag(init_pos, ok as usize);
+    if let Some(mistakes) = state.puzzle.as_mut().filter(|_| flagged) {
+        // Flags are unknown cells to the solver, so this is the proof before the flag.
+        if !solver::probabilities(conf, &state.map)[at].is_some_and(solver::is_mine) {
+            mistakes.flags += 1;
+            pos = draw::ferris_says_puzzle(init_pos, "Nothing proves that flag.");
+        }
+    }
+    let pos = draw::show_bomb_status(&pos, conf.bomb - state.flag_num);
+    draw::show_map(&pos, conf, &state.map, draw::ShowMode::Normal);
 }
 
-fn flag_event(
+/// Point the cursor at a safe cell (or a mine to flag), the best guess if none is sure.
+/// The pattern proving it is named, and its numbers lit up. Return the cell pointed at.
+fn hint_event(
     init_pos: &draw::Pos,
-    init_at: &draw::Pos,
-    now_at: &draw::Pos,
+    map_pos: &draw::Pos,
     conf: &config::GameConfig,
-    init_mine: bool,
-    graph_map: Vec<map::Cell>,
-    flag_num: &mut usize,
-) -> Vec<map::Cell> {
-    if init_mine {
-        draw::ferris_says_flag(init_pos, 0);
-        return graph_map;
+    state: &mut GameState,
+    at: usize,
+) -> usize {
+    if state.init_mine {
+        draw::ferris_says_hint(init_pos, "The first cell is always safe.");
+        return at;
+    }
+    let Some(step) = bot::think(conf, &state.map, at) else {
+        return at;
+    };
+    state.hints += 1;
+    let pos = draw::ferris_says_hint(init_pos, &step.words);
+    draw::show_hint_status(&pos, state.hints);
+    read_numbers(map_pos, conf, &state.map, &step.numbers);
+    step.cell
+}
+
+/// Light up the numbers a reason reads, and read them out.
+fn read_numbers(
+    map_pos: &draw::Pos,
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    numbers: &[usize],
+) {
+    if numbers.is_empty() {
+        return;
+    }
+    draw::show_highlight(map_pos, conf, map, numbers);
+    let names: Vec<String> = numbers
+        .iter()
+        .map(|&pos| map::cell_name(pos, conf))
+        .collect();
+    draw::announce(&format!("Read {}.", names.join(", ")));
+}
+
+/// Let the bot make one move, through the same events as a player.
+/// Return if the game is over, None if the bot has nothing to do.
+fn bot_event(
+    init_pos: &draw::Pos,
+    map_pos: &draw::Pos,
+    conf: &config::GameConfig,
+    rng: &mut ThreadRng,
+    state: &mut GameState,
+    at: &mut usize,
+) -> Option<bool> {
+    let Some(step) = bot::think(conf, &state.map, *at) else {
+        draw::ferris_says_bot(init_pos, "Nothing I can do.");
+        return None;
+    };
+    *at = step.cell;
+    // A game the bot helped makes no record, like hints.
+    state.hints += 1;
+    let first = state.init_mine;
+    let over = match step.action {
+        bot::Action::Open => mine_event(init_pos, map_pos, conf, rng, state, step.cell, false),
+        bot::Action::Flag => {
+            flag_event(init_pos, conf, state, step.cell);
+            false
+        }
+    };
+    if !over {
+        let words = if first {
+            "The first cell is always safe."
+        } else {
+            &step.words
+        };
+        let pos = draw::ferris_says_bot(init_pos, words);
+        draw::show_hint_status(&pos, state.hints);
+        read_numbers(map_pos, conf, &state.map, &step.numbers);
     }
-    let (graph_map, ok) = map::flag_map(init_at, now_at, conf, graph_map, flag_num);
-    let pos = draw::ferris_says_flag(init_pos, ok as usize);
-    let pos = draw::show_bomb_status(&pos, conf.bomb - *flag_num);
-    draw::show_map(&pos, conf, &graph_map, draw::ShowMode::Normal);
+    Some(over)
+}
+
+/// Tell whether the open that lost was a forced guess or a missed deduction.
+fn analyse(
+    map_pos: &draw::Pos,
+    conf: &config::GameConfig,
+    before: &[map::Cell],
+    after: &[map::Cell],
+) -> Analysis {
+    // A chord may open more than one mine, blame the first.
+    let fatal = (0..after.len())
+        .find(|&pos| {
+            before[pos].surface != map::Surface::Open
+                && after[pos].surface == map::Surface::Open
+                && after[pos].content.is_mine()
+        })
+        .unwrap_or_default();
+    let (words, better) = judge(conf, before, fatal);
+    Analysis {
+        words,
+        better: better.map(|on| draw::cell_pos(map_pos, conf, on)),
+    }
+}
+
+/// What to say about opening the mine at `fatal`, and the cell that was better to open.
+/// A proven mine is a missed deduction, a better guess leaves the proven mines out, like the bot.
+fn judge(conf: &config::GameConfig, before: &[map::Cell], fatal: usize) -> (String, Option<usize>) {
+    let name = map::cell_name(fatal, conf);
+    let probs = solver::probabilities(conf, before);
+    let risk = probs[fatal].unwrap_or(1.0) * 100.0;
+    if let Some(solver::Hint::Safe(on)) = solver::hint_from(conf, before, &probs, fatal) {
+        let why = match pattern::explain(conf, before, on) {
+            Some(deduction) => deduction.pattern.to_string(),
+            None => "every layout agrees".to_string(),
+        };
+        let safe = map::cell_name(on, conf);
+        let words = if solver::is_mine(risk / 100.0) {
+            format!(
+                "Missed it! {} was a mine, {} was safe: {}.",
+                name, safe, why
+            )
+        } else {
+            format!(
+                "Missed it! {} was {:.0}%, {} was safe: {}.",
+                name, risk, safe, why
+            )
+        };
+        return (words, Some(on));
+    }
+    let guesses: Vec<Option<f64>> = probs
+        .into_iter()
+        .map(|p| p.filter(|&p| !solver::is_mine(p)))
+        .collect();
+    let guess = match solver::hint_from(conf, before, &guesses, fatal) {
+        Some(solver::Hint::Guess(on, p)) if on != fatal => Some((on, p)),
+        _ => None,
+    };
+    if solver::is_mine(risk / 100.0) {
+        return match guess {
+            Some((on, p)) => (
+                format!(
+                    "Missed it! {} was a proven mine, {} was only {:.0}%.",
+                    name,
+                    map::cell_name(on, conf),
+                    p * 100.0
+                ),
+                Some(on),
+            ),
+            None => (format!("Missed it! {} was a proven mine.", name), None),
+        };
+    }
+    match guess {
+        Some((on, p)) if p * 100.0 < risk - 0.5 => (
+            format!(
+                "A forced guess, but {} was {:.0}% and {} only {:.0}%.",
+                name,
+                risk,
+                map::cell_name(on, conf),
+                p * 100.0
+            ),
+            Some(on),
+        ),
+        _ => (
+            format!("A forced guess, {} was {:.0}%, the best odds.", name, risk),
+            None,
+        ),
+    }
+}
 
-    graph_map
+/// Keep how a daily game ended if it's the ranked one, and tell the streak.
+/// Failing to keep it is told too, the game goes on.
+pub fn daily_end_event(challenge: &daily::Challenge, finished: &Finished) {
+    let init_pos = draw::Pos(1, 1);
+    let outcome = match finished.mode {
+        draw::ShowMode::Win => "won",
+        draw::ShowMode::Lose => "lost",
+        _ => "left",
+    };
+    if !challenge.ranked {
+        draw::ferris_says_daily(&init_pos, "Just for fun, today's result is kept.");
+        return;
+    }
+    let kept = daily::finish(
+        &challenge.date,
+        challenge.mode,
+        outcome,
+        finished.secs,
+        finished.hints,
+    )
+    .and_then(|_| daily::history());
+    let words = match kept {
+        Ok(records) => format!(
+            "Daily {} in {}s, {} days in a row.",
+            outcome,
+            finished.secs,
+            daily::streak(&records, &challenge.date)
+        ),
+        Err(err) => err.to_string(),
+    };
+    draw::ferris_says_daily(&init_pos, &words);
 }
 
 /// End menu event, return usize meanings:
 /// 0: retry
 /// 1: back
+///
+/// The other items export or share the board, or show the analysis of a lost game,
+/// and stay in the menu.
 pub fn end_event(
-    pos: &draw::Pos,
+    finished: &Finished,
     key_conf: &config::GlobleConfig,
     game_config: &config::GameConfig,
-    stdin: &Stdin,
-    stdout: &mut MouseTerminal<RawTerminal<Stdout>>,
-) -> usize {
+    input: &Input,
+    stdout: &mut Terminal,
+) -> Result<usize> {
+    const EXPORTS: [export::Format; 3] = [
+        export::Format::Text,
+        export::Format::Ansi,
+        export::Format::Svg,
+    ];
     let init_pos = draw::Pos(1, 1);
-    let pos = draw::Pos(pos.0 + 1, pos.1 - game_config.height as u16 - 1);
-    let opts = vec!["Retry".to_string(), "Go Back".to_string()];
+    let pos = draw::Pos(
+        finished.pos.0 + 1,
+        finished.pos.1 - game_config.height as u16 - 1,
+    );
+    let mut opts = vec!["Retry".to_string(), "Go Back".to_string()];
+    opts.extend(["Export text", "Export ANSI", "Export SVG", "Share"].map(String::from));
+    if finished.analysis.is_some() {
+        opts.push("Analyse".to_string());
+    }
     let mut selected_item_idx = 0;
-    let last_pos = draw::show_menu(&pos, &opts, selected_item_idx).expect("cannot show end menu");
+    let last_pos = draw::show_menu(&pos, &opts, selected_item_idx)?;
+    let code = board::to_code(game_config, &finished.map);
+    // Under the map, or under the menu when it's longer.
+    let under = draw::Pos(1, finished.pos.1.max(last_pos.1) + 1);
+    let below = draw::show_text(&under, &format!("code {}", code));
+    // Act on an item that stays in the menu.
+    let stay = |idx: usize, stdout: &mut Terminal| -> Result<()> {
+        match (idx - 2, &finished.analysis) {
+            (i, _) if i < EXPORTS.len() => {
+                let words =
+                    match export::save(game_config, &finished.map, &finished.mode, EXPORTS[i]) {
+                        Ok(path) => format!("Saved to {}.", path),
+                        Err(err) => err.to_string(),
+                    };
+                draw::ferris_says_command(&init_pos, &words);
+            }
+            (3, _) => {
+                let text = export::summary(
+                    game_config,
+                    &finished.map,
+                    &finished.mode,
+                    finished.secs,
+                    &code,
+                    &daily::today(),
+                );
+                // Screen readers get the text, not a clipboard they can't check.
+                if key_conf.clipboard && !draw::is_accessible() {
+                    term::copy(stdout, &text)?;
+                    draw::ferris_says_command(&init_pos, "Copied to the clipboard.");
+                } else {
+                    draw::ferris_says_command(&init_pos, "Here it is, copy it.");
+                    draw::show_text(&below, &text);
+                }
+            }
+            (_, Some(analysis)) => {
+                draw::ferris_says_analysis(&init_pos, &analysis.words);
+                if let Some(better) = &analysis.better {
+                    draw::set_cursor(better);
+                }
+            }
+            (_, None) => (),
+        }
+        Ok(())
+    };
 
-    stdout.flush().unwrap();
-    for c in stdin.lock().events() {
-        let evt = c.unwrap();
+    draw::present(stdout)?;
+    for c in input.events() {
+        let evt = c?;
         match evt {
             Event::Key(key) => {
                 match key {
-                    Key::Up => selected_item_idx = selected_item_idx.wrapping_sub(1),
-                    Key::Down => selected_item_idx = selected_item_idx.wrapping_add(1),
+                    Key::Up => selected_item_idx += opts.len() - 1,
+                    Key::Down => selected_item_idx += 1,
                     Key::Char(char) => {
                         if char == key_conf.up_key {
-                            selected_item_idx = selected_item_idx.wrapping_sub(1)
+                            selected_item_idx += opts.len() - 1
                         } else if char == key_conf.down_key {
-                            selected_item_idx = selected_item_idx.wrapping_add(1)
+                            selected_item_idx += 1
                         } else if char == key_conf.quit_key {
                             draw::ferris_says_quit(&init_pos);
-                            return 255;
+                            return Ok(255);
                         } else if char == key_conf.mine_key {
-                            return selected_item_idx;
+                            if selected_item_idx < 2 {
+                                return Ok(selected_item_idx);
+                            }
+                            stay(selected_item_idx, stdout)?;
+                            draw::present(stdout)?;
+                            continue;
                         }
                     }
                     _ => (),
                 }
                 selected_item_idx %= opts.len();
                 draw::ferris_says_end(&init_pos, selected_item_idx);
-                draw::show_menu(&pos, &opts, selected_item_idx).expect("cannot show end menu");
+                draw::show_menu(&pos, &opts, selected_item_idx)?;
             }
             Event::Mouse(MouseEvent::Press(btn, x, y)) => {
                 match btn {
                     MouseButton::Left => {
                         let x = (pos.0 + 1).max((last_pos.0 - 1).min(x));
                         let y = (pos.1 + 1).max((last_pos.1 - 1).min(y));
-                        let selected_item_idx = (y - pos.1 - 1) as usize;
+                        selected_item_idx = (y - pos.1 - 1) as usize;
 
                         draw::ferris_says_end(&init_pos, selected_item_idx);
-                        draw::show_menu(&pos, &opts, selected_item_idx)
-                            .expect("cannot show end menu");
-                        print!("{}", cursor::Goto(x, y));
+                        draw::show_menu(&pos, &opts, selected_item_idx)?;
+                        draw::set_cursor(&draw::Pos(x, y));
 
-                        return selected_item_idx;
+                        if selected_item_idx < 2 {
+                            return Ok(selected_item_idx);
+                        }
+                        stay(selected_item_idx, stdout)?;
+                        draw::present(stdout)?;
+                        continue;
                     }
-                    MouseButton::WheelUp => selected_item_idx = selected_item_idx.wrapping_sub(1),
-                    MouseButton::WheelDown => selected_item_idx = selected_item_idx.wrapping_add(1),
+                    MouseButton::WheelUp => selected_item_idx += opts.len() - 1,
+                    MouseButton::WheelDown => selected_item_idx += 1,
                     MouseButton::Right => {
                         draw::ferris_says_quit(&init_pos);
-                        return 255;
+                        return Ok(255);
                     }
                     _ => (),
                 }
                 selected_item_idx %= opts.len();
                 draw::ferris_says_end(&init_pos, selected_item_idx);
-                draw::show_menu(&pos, &opts, selected_item_idx).expect("cannot show end menu");
+                draw::show_menu(&pos, &opts, selected_item_idx)?;
             }
             _ => (),
         }
-        stdout.flush().unwrap();
+        draw::present(stdout)?;
     }
-    255
+    Ok(255)
 }
 
 pub async fn time_record_event(pos: draw::Pos, conf: config::GameConfig) {
@@ -360,5 +1475,38 @@ pub async fn time_record_event(pos: draw::Pos, conf: config::GameConfig) {
         interval.tick().await;
         time += 1;
         draw::show_time_status(&pos, &conf, time);
+        let _ = draw::present(&mut std::io::stdout());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::judge;
+    use crate::board;
+
+    #[test]
+    fn judge_proven_mines() {
+        // The 1 has a mine on one side, so with two mines the last cell is surely one.
+        let (conf, map) = board::from_text("*o.*\n").unwrap();
+        assert_eq!(
+            (
+                "Missed it! D1 was a proven mine, C1 was only 50%.".to_string(),
+                Some(2)
+            ),
+            judge(&conf, &map, 3)
+        );
+        assert_eq!(
+            (
+                "A forced guess, A1 was 50%, the best odds.".to_string(),
+                None
+            ),
+            judge(&conf, &map, 0)
+        );
+        // Every covered cell is a mine, nothing was better.
+        let (conf, map) = board::from_text("*o\n**\n").unwrap();
+        assert_eq!(
+            ("Missed it! A1 was a proven mine.".to_string(), None),
+            judge(&conf, &map, 0)
+        );
     }
 }
@@ -0,0 +1,340 @@
+//! Write a finished board to a file, as plain text, ANSI colours or an SVG image.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use termion::{color, style};
+
+use crate::config::GameMode;
+use crate::draw::ShowMode;
+use crate::{config, daily, map, Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Ansi,
+    Svg,
+}
+
+impl Format {
+    fn extension(&self) -> &str {
+        match self {
+            Format::Text => "txt",
+            Format::Ansi => "ans",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+/// How a cell is painted, the colours are those of the terminal.
+#[derive(Debug, PartialEq, Eq)]
+enum Paint {
+    Plain,
+    Number(usize),
+    Flag,
+    WrongFlag,
+    Mine,
+    /// The mine that lost the game.
+    Clicked,
+}
+
+/// The symbol of a cell at the end of a game, like the screen shows it.
+fn look(cell: &map::Cell, mode: &ShowMode) -> (char, Paint) {
+    // One mine or one flag has its symbol, more show how many.
+    let many = |n: u8, one: char| if n == 1 { one } else { map::glyph(n) };
+    let content = |cell: &map::Cell| match cell.content {
+        map::Content::Number(0) => (' ', Paint::Plain),
+        map::Content::Bomb(n) => (many(n, '*'), Paint::Mine),
+        map::Content::Number(n) => (map::glyph(n), Paint::Number(n as usize)),
+    };
+    let surface = |cell: &map::Cell| match cell.surface {
+        map::Surface::Cover => ('▓', Paint::Plain),
+        map::Surface::Flag(n) => (many(n, 'P'), Paint::Flag),
+        map::Surface::Open => content(cell),
+    };
+    let mine = cell.content.is_mine();
+    let mines = cell.content.count() as u8;
+    match mode {
+        ShowMode::Normal => surface(cell),
+        ShowMode::All => content(cell),
+        ShowMode::Win if mine => (many(mines, 'P'), Paint::Flag),
+        ShowMode::Win => content(cell),
+        ShowMode::Lose => match (&cell.surface, mine) {
+            (map::Surface::Open, true) => (many(mines, '*'), Paint::Clicked),
+            (map::Surface::Cover, true) => (many(mines, '*'), Paint::Mine),
+            (map::Surface::Flag(_), false) => ('X', Paint::WrongFlag),
+            _ => surface(cell),
+        },
+    }
+}
+
+fn end(mode: &ShowMode) -> &'static str {
+    match mode {
+        ShowMode::Win => "won",
+        ShowMode::Lose => "lost",
+        _ => "left",
+    }
+}
+
+fn headline(conf: &config::GameConfig, mode: &ShowMode) -> String {
+    format!("{}, {} mines, {}", conf.size_name(), conf.bomb, end(mode))
+}
+
+fn ansi(ch: char, paint: &Paint) -> String {
+    let start = match paint {
+        Paint::Plain | Paint::Mine => String::new(),
+        // Numbers are coloured the way the map shows them.
+        Paint::Number(n) => return map::Content::Number(*n as u8).to_string(),
+        Paint::Flag => color::Fg(color::Yellow).to_string(),
+        Paint::WrongFlag => color::Fg(color::LightRed).to_string(),
+        Paint::Clicked => format!("{}{}", color::Fg(color::Black), color::Bg(color::Red)),
+    };
+    format!("{}{}{}", start, ch, style::Reset)
+}
+
+/// The colours of an xterm, for the ones the screen uses.
+fn rgb(paint: &Paint) -> &'static str {
+    match paint {
+        Paint::Plain | Paint::Mine => "#e5e5e5",
+        Paint::Number(1) => "#5c5cff",
+        Paint::Number(2) => "#00ff00",
+        Paint::Number(3) => "#ff0000",
+        Paint::Number(4) => "#cd00cd",
+        Paint::Number(5) => "#cd0000",
+        Paint::Number(6) => "#00cd00",
+        Paint::Number(7) => "#0000ee",
+        Paint::Number(8) => "#00cdcd",
+        Paint::Number(_) => "#ff00ff",
+        Paint::Flag => "#cdcd00",
+        Paint::WrongFlag => "#ff0000",
+        Paint::Clicked => "#000000",
+    }
+}
+
+/// Render a finished board, shown the way `mode` shows it on the screen.
+pub fn render(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    mode: &ShowMode,
+    format: Format,
+) -> String {
+    let mut out = String::new();
+    match format {
+        Format::Text | Format::Ansi => {
+            let _ = writeln!(out, "{}", headline(conf, mode));
+            let hex = conf.topology == config::Topology::Hex;
+            for (y, row) in map.chunks(conf.width).enumerate() {
+                // Layers are apart like in the board text format.
+                if y > 0 && y % conf.height == 0 {
+                    out.push('\n');
+                }
+                for (x, cell) in row.iter().enumerate() {
+                    // Hex rows are spaced out like on the screen, the odd ones of each layer offset.
+                    if hex && (x > 0 || y % conf.height % 2 == 1) {
+                        out.push(' ');
+                    }
+                    let (ch, paint) = look(cell, mode);
+                    if format == Format::Text {
+                        // Without colours, the mine that lost needs a symbol of its own.
+                        out.push(if paint == Paint::Clicked { '#' } else { ch });
+                    } else {
+                        out.push_str(&ansi(ch, &paint));
+                    }
+                }
+                out.push('\n');
+            }
+        }
+        Format::Svg => {
+            const CELL: usize = 20;
+            // Row within its layer, like the text.
+            let shift = |row: usize| match conf.topology {
+                config::Topology::Hex => row % 2 * CELL / 2,
+                _ => 0,
+            };
+            // Layers go down one under another, a row apart.
+            let (width, height) = (
+                conf.width * CELL + shift(1),
+                (conf.layers * (conf.height + 1)) * CELL,
+            );
+            let _ = writeln!(
+                out,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="14" text-anchor="middle">"#,
+                width, height
+            );
+            let _ = writeln!(
+                out,
+                r##"<rect width="100%" height="100%" fill="#000000"/>"##
+            );
+            let _ = writeln!(
+                out,
+                r##"<text x="{}" y="15" fill="#e5e5e5">{}</text>"##,
+                width / 2,
+                headline(conf, mode)
+            );
+            for (idx, cell) in map.iter().enumerate() {
+                let (row, col) = (idx / conf.width, idx % conf.width);
+                let (layer, row) = (row / conf.height, row % conf.height);
+                let y = (layer * (conf.height + 1) + row + 1) * CELL;
+                let x = col * CELL + shift(row);
+                let (ch, paint) = look(cell, mode);
+                let fill = match (&paint, ch) {
+                    (Paint::Clicked, _) => "#cd0000",
+                    (_, '▓') => "#7f7f7f",
+                    _ => "#1c1c1c",
+                };
+                let _ = writeln!(
+                    out,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                    x + 1,
+                    y + 1,
+                    CELL - 2,
+                    CELL - 2,
+                    fill
+                );
+                if ch != ' ' && ch != '▓' {
+                    let _ = writeln!(
+                        out,
+                        r#"<text x="{}" y="{}" fill="{}">{}</text>"#,
+                        x + CELL / 2,
+                        y + 15,
+                        rgb(&paint),
+                        ch
+                    );
+                }
+            }
+            out.push_str("</svg>\n");
+        }
+    }
+    out
+}
+
+/// The name of a board's difficulty, or its size for a board of its own.
+fn difficulty(conf: &config::GameConfig) -> String {
+    [GameMode::Simple, GameMode::Normal, GameMode::Hard]
+        .into_iter()
+        .find(|&mode| {
+            let known = config::GameConfig::from_game_mode(mode);
+            (known.width, known.height, known.bomb, 1)
+                == (conf.width, conf.height, conf.bomb, conf.layers)
+        })
+        .map_or_else(
+            || format!("{}/{}", conf.size_name(), conf.bomb),
+            |mode| mode.to_string(),
+        )
+}
+
+/// The mini grid of a shared result, one emoji a cell.
+fn emoji(paint: &Paint, ch: char) -> char {
+    match (paint, ch) {
+        (Paint::Flag, _) => '🚩',
+        (Paint::WrongFlag, _) => '❌',
+        (Paint::Mine, _) => '💣',
+        (Paint::Clicked, _) => '💥',
+        (_, '▓') => '⬛',
+        _ => '🟩',
+    }
+}
+
+/// A short text to share a finished game: what was played, how fast,
+/// when, the board code to play it again, and the board in emoji.
+pub fn summary(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    mode: &ShowMode,
+    secs: u64,
+    code: &str,
+    date: &daily::Date,
+) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "rua {}, {} in {}s", difficulty(conf), end(mode), secs);
+    let three_bv = map::three_bv(conf, map);
+    if *mode == ShowMode::Win {
+        let _ = write!(out, ", 3BV/s {:.2}", three_bv as f64 / secs.max(1) as f64);
+    } else {
+        let _ = write!(out, ", 3BV {}", three_bv);
+    }
+    let _ = writeln!(out, "\n{}, code {}", date, code);
+    for (y, row) in map.chunks(conf.width).enumerate() {
+        if y > 0 && y % conf.height == 0 {
+            out.push('\n');
+        }
+        for cell in row {
+            let (ch, paint) = look(cell, mode);
+            out.push(emoji(&paint, ch));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Write a finished board to a new file in the current directory, return its path.
+pub fn save(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    mode: &ShowMode,
+    format: Format,
+) -> Result<String> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = format!("rua-{}.{}", stamp, format.extension());
+    fs::write(&path, render(conf, map, mode, format))
+        .map_err(|e| Error::Save(format!("cannot write {}: {}", path, e)))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render, summary, Format};
+    use crate::board;
+    use crate::draw::ShowMode;
+
+    #[test]
+    fn text_after_a_loss() {
+        let (conf, mut map) = board::from_text("*.f\nFoo\n").unwrap();
+        map[0].surface = crate::map::Surface::Open;
+        let text = render(&conf, &map, &ShowMode::Lose, Format::Text);
+        assert_eq!("3x2, 2 mines, lost\n#▓X\nP2 \n", text);
+    }
+
+    #[test]
+    fn summary_after_a_win() {
+        let (conf, map) = board::from_text("*.\n..\n").unwrap();
+        let date = crate::daily::today();
+        let text = summary(&conf, &map, &ShowMode::Win, 4, "2x2.gA", &date);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!("rua 2x2/1, won in 4s, 3BV/s 0.75", lines[0]);
+        assert_eq!(format!("{}, code 2x2.gA", date), lines[1]);
+        assert_eq!(["🚩🟩", "🟩🟩"], lines[2..]);
+    }
+
+    #[test]
+    fn svg_has_every_cell() {
+        let (conf, map) = board::from_text("*.\n..\n").unwrap();
+        let svg = render(&conf, &map, &ShowMode::Win, Format::Svg);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert_eq!(1 + 4, svg.matches("<rect").count());
+    }
+
+    #[test]
+    fn hex_layers_start_even() {
+        // Three rows a layer, the second layer starts on an even row again.
+        let (conf, map) = board::from_text("# hex\n*.\n..\n..\n\n..\n..\n.*\n").unwrap();
+        let text = render(&conf, &map, &ShowMode::Win, Format::Text);
+        let rows: Vec<&str> = text.lines().skip(1).filter(|row| !row.is_empty()).collect();
+        let offset: Vec<bool> = rows.iter().map(|row| row.starts_with(' ')).collect();
+        assert_eq!([false, true, false, false, true, false], offset[..]);
+        let svg = render(&conf, &map, &ShowMode::Win, Format::Svg);
+        let xs: Vec<&str> = svg
+            .lines()
+            .filter_map(|line| line.strip_prefix("<rect x=\""))
+            .map(|rest| rest.split('"').next().unwrap())
+            .collect();
+        // Each layer: rows 0 and 2 at the edge, row 1 half a cell in.
+        assert_eq!(
+            ["1", "11", "1", "1", "11", "1"],
+            [xs[0], xs[2], xs[4], xs[6], xs[8], xs[10]]
+        );
+    }
+}
@@ -1,4 +1,18 @@
+pub mod bench;
+pub mod board;
+pub mod bot;
+pub mod command;
 pub mod config;
-pub mod map;
+pub mod daily;
 pub mod draw;
+pub mod editor;
+pub mod error;
 pub mod event;
+pub mod export;
+pub mod map;
+pub mod pattern;
+pub mod puzzle;
+pub mod solver;
+pub mod term;
+
+pub use error::{Error, Result};
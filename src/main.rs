@@ -1,43 +1,107 @@
-use std::io::{stdin, stdout};
+use std::io::{stdout, Write};
+use std::{env, process};
 
 use rua::{
-    config, draw,
-    event::{self},
+    bench,
+    command::Prompt,
+    config, daily, draw,
+    event::{self, GameEnd, GameSetup},
+    term,
 };
-use termion::{input::MouseTerminal, raw::IntoRawMode};
+
+const USAGE: &str = "usage: rua [load FILE | puzzle FILE | daily | bench ...]";
 
 fn main() {
-    let key_config = config::globle_config_from_env();
+    if let Err(err) = run() {
+        // `play` restored the terminal when leaving, make sure that's on the screen first.
+        let _ = stdout().flush();
+        eprintln!("rua: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> rua::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => play(None),
+        Some("load") => match &args[1..] {
+            [path] => play(Some(GameSetup::load(path)?)),
+            _ => Err(rua::Error::Usage(USAGE.to_string())),
+        },
+        Some("puzzle") => match &args[1..] {
+            [path] => play(Some(GameSetup::load_puzzle(path)?)),
+            _ => Err(rua::Error::Usage(USAGE.to_string())),
+        },
+        Some("daily") => {
+            let records = daily::history()?;
+            print!("{}", daily::report(&records, &daily::today()));
+            Ok(())
+        }
+        Some("bench") => bench::run(&args[1..]),
+        Some(arg) => Err(rua::Error::Usage(format!(
+            "unknown argument {}\n{}",
+            arg, USAGE
+        ))),
+    }
+}
+
+/// Play games, the first on the given board if any.
+fn play(mut first: Option<GameSetup>) -> rua::Result<()> {
+    let key_config = config::globle_config_from_env()?;
     let init_pos = draw::Pos(1, 1);
+    draw::set_accessible(key_config.accessible);
 
-    let stdin = stdin();
-    let mut stdout = MouseTerminal::from(stdout().into_raw_mode().unwrap());
+    let mut stdout = term::setup()?;
+    let _restore = term::Restore;
+    let input = term::Input::spawn();
     let mut rng = rand::thread_rng();
+    let mut prompt = Prompt::default();
 
     // All event start
     'start: loop {
         // Choose a difficulty.
         draw::clean_output();
-        let option = event::entry_event(&init_pos, &key_config, &stdin, &mut stdout);
-        if let None = option {
+        let option = match first.take() {
+            Some(setup) => Some(setup),
+            None => event::entry_event(&init_pos, &key_config, &input, &mut stdout)?,
+        };
+        let mut setup = match option {
+            Some(setup) => setup,
             // exit from press q.
-            return;
-        }
-
-        let game_config = option.unwrap();
+            None => break 'start,
+        };
 
         'game: loop {
             // Game start.
             draw::clean_output();
-            let pos = event::game_event(&key_config, &game_config, &stdin, &mut stdout, &mut rng);
+            let end = event::game_event(
+                &key_config,
+                &setup,
+                &input,
+                &mut stdout,
+                &mut rng,
+                &mut prompt,
+            )?;
+            // Only the first daily game of the day is ranked.
+            if let Some(challenge) = &mut setup.daily {
+                if let GameEnd::Over(finished) = &end {
+                    event::daily_end_event(challenge, finished);
+                }
+                challenge.ranked = false;
+            }
+            let finished = match end {
+                GameEnd::Over(finished) => finished,
+                GameEnd::Restart => continue,
+                GameEnd::Quit => break 'start,
+            };
 
             // End menu.
-            match event::end_event(&pos, &key_config, &game_config, &stdin, &mut stdout) {
+            match event::end_event(&finished, &key_config, &setup.conf, &input, &mut stdout)? {
                 0 => continue,
                 1 => break 'game,
                 _ => break 'start,
             }
         }
     }
-    draw::put_cursor_bottom();
+    Ok(())
 }
@@ -1,32 +1,29 @@
 #![allow(dead_code)]
 
+use std::fmt;
+
 use queues::{queue, IsQueue, Queue};
-use rand::{rngs::ThreadRng, Rng};
+use rand::Rng;
 use termion::{color, style};
 
-use crate::{config, draw};
+use crate::config;
 
 /// The content type of a cell.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Content {
-    Zero,
-    One,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Bomb,
+    /// A safe cell, with the number of mines around it.
+    Number(u8),
+    /// A cell with mines, one unless cells hold several.
+    Bomb(u8),
 }
 
 /// The surface type of a cell.
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Surface {
     Cover,
     Open,
-    Flag,
+    /// Flagged, with how many mines the player puts there.
+    Flag(u8),
 }
 
 /// Cell is the minimal member of map.
@@ -36,32 +33,53 @@ pub struct Cell {
     pub surface: Surface,
 }
 
-impl ToString for Surface {
-    fn to_string(&self) -> String {
+/// The char of a count: digits, then letters from 10 on, e.g. 'c' for 12.
+pub fn glyph(count: u8) -> char {
+    char::from_digit(count as u32, 36).unwrap_or('?')
+}
+
+impl fmt::Display for Surface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Surface::Cover => format!("▓"),
-            Surface::Flag => format!("{}P{}", color::Fg(color::Yellow), style::Reset),
-            _ => format!(" "),
+            Surface::Cover => write!(f, "▓"),
+            Surface::Flag(1) => write!(f, "{}P{}", color::Fg(color::Yellow), style::Reset),
+            Surface::Flag(n) => write!(
+                f,
+                "{}{}{}{}",
+                color::Fg(color::Black),
+                color::Bg(color::Yellow),
+                glyph(*n),
+                style::Reset
+            ),
+            _ => write!(f, " "),
         }
     }
 }
 
-impl ToString for Content {
-    fn to_string(&self) -> String {
+impl fmt::Display for Content {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Content::Zero => format!(" "),
-            Content::One => format!("{}1{}", color::Fg(color::LightBlue), style::Reset),
-            Content::Two => format!("{}2{}", color::Fg(color::LightGreen), style::Reset),
-            Content::Three => format!("{}3{}", color::Fg(color::LightRed), style::Reset),
-            Content::Four => format!("{}4{}", color::Fg(color::Magenta), style::Reset),
-            Content::Five => format!("{}5{}", color::Fg(color::Red), style::Reset),
-            Content::Six => format!("{}6{}", color::Fg(color::Green), style::Reset),
-            Content::Seven => format!("{}7{}", color::Fg(color::Blue), style::Reset),
-            Content::Eight => format!("{}8{}", color::Fg(color::Cyan), style::Reset),
-            Content::Bomb => format!(
-                "{}{}*{}",
+            Content::Number(0) => write!(f, " "),
+            Content::Number(n) => {
+                let paint = match n {
+                    1 => color::Fg(color::LightBlue).to_string(),
+                    2 => color::Fg(color::LightGreen).to_string(),
+                    3 => color::Fg(color::LightRed).to_string(),
+                    4 => color::Fg(color::Magenta).to_string(),
+                    5 => color::Fg(color::Red).to_string(),
+                    6 => color::Fg(color::Green).to_string(),
+                    7 => color::Fg(color::Blue).to_string(),
+                    8 => color::Fg(color::Cyan).to_string(),
+                    _ => color::Fg(color::LightMagenta).to_string(),
+                };
+                write!(f, "{}{}{}", paint, glyph(*n), style::Reset)
+            }
+            Content::Bomb(n) => write!(
+                f,
+                "{}{}{}{}",
                 color::Fg(color::Black),
                 color::Bg(color::Red),
+                if *n == 1 { '*' } else { glyph(*n) },
                 style::Reset
             ),
         }
@@ -69,27 +87,37 @@ impl ToString for Content {
 }
 
 impl Content {
-    pub fn from_i8(num: i8) -> Content {
-        match num {
-            0 => Content::Zero,
-            1 => Content::One,
-            2 => Content::Two,
-            3 => Content::Three,
-            4 => Content::Four,
-            5 => Content::Five,
-            6 => Content::Six,
-            7 => Content::Seven,
-            8 => Content::Eight,
-            _ => Content::Bomb,
+    /// The mines a number counts around, or the mines in the cell.
+    pub fn count(&self) -> usize {
+        match self {
+            Content::Number(n) | Content::Bomb(n) => *n as usize,
         }
     }
+
+    pub fn is_mine(&self) -> bool {
+        matches!(self, Content::Bomb(_))
+    }
 }
 
-impl ToString for Cell {
-    fn to_string(&self) -> String {
+impl Surface {
+    pub fn is_flag(&self) -> bool {
+        matches!(self, Surface::Flag(_))
+    }
+
+    /// The mines a flag stands for, 0 without one.
+    pub fn flags(&self) -> usize {
+        match self {
+            Surface::Flag(n) => *n as usize,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.surface {
-            Surface::Open => self.content.to_string(),
-            _ => self.surface.to_string(),
+            Surface::Open => self.content.fmt(f),
+            _ => self.surface.fmt(f),
         }
     }
 }
@@ -106,6 +134,182 @@ impl Cell {
     }
 }
 
+impl Cell {
+    /// Describe the cell in words, e.g. "covered" or "3".
+    pub fn describe(&self) -> String {
+        match (&self.surface, &self.content) {
+            (Surface::Cover, _) => "covered".to_string(),
+            (Surface::Flag(1), _) => "flagged".to_string(),
+            (Surface::Flag(n), _) => format!("flagged {} times", n),
+            (Surface::Open, Content::Number(0)) => "blank".to_string(),
+            (Surface::Open, Content::Bomb(1)) => "mine".to_string(),
+            (Surface::Open, Content::Bomb(n)) => format!("{} mines", n),
+            (Surface::Open, Content::Number(n)) => n.to_string(),
+        }
+    }
+}
+
+/// The words of numbers (one and many), used to read out opened cells.
+#[rustfmt::skip]
+const NUMBER_WORDS: [(&str, &str); 9] = [
+    ("blank", "blank"), ("one", "ones"), ("two", "twos"),
+    ("three", "threes"), ("four", "fours"), ("five", "fives"),
+    ("six", "sixes"), ("seven", "sevens"), ("eight", "eights"),
+];
+
+fn count_words(count: usize, (one, many): (&str, &str)) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+/// Describe a cell and its neighbours in words, e.g. "C7, covered, 2 flags adjacent".
+pub fn describe_at(conf: &config::GameConfig, map: &[Cell], pos: usize) -> String {
+    let (mut flags, mut covers) = (0, 0);
+    check_around_fn(conf, pos, |pos| match map[pos].surface {
+        Surface::Flag(n) => flags += n as usize,
+        Surface::Cover => covers += 1,
+        Surface::Open => (),
+    });
+    let mut words = format!("{}, {}", cell_name(pos, conf), map[pos].describe());
+    match map[pos].surface {
+        Surface::Open if covers > 0 => {
+            let flags = count_words(flags, ("flag", "flags"));
+            let covers = count_words(covers, ("covered", "covered"));
+            words += &format!(", {} and {} adjacent", flags, covers);
+        }
+        Surface::Cover if flags > 0 => {
+            words += &format!(", {} adjacent", count_words(flags, ("flag", "flags")));
+        }
+        _ => (),
+    }
+    words
+}
+
+/// Summarise the cells opened between two states of a map, e.g.
+/// "Opened 12 cells: 6 blank, 4 ones, 2 twos".
+pub fn describe_opened(before: &[Cell], after: &[Cell]) -> String {
+    let mut counts = vec![];
+    let mut opened = 0;
+    for (old, new) in before.iter().zip(after) {
+        if old.surface != Surface::Open && new.surface == Surface::Open {
+            opened += 1;
+            let n = new.content.count();
+            if counts.len() <= n {
+                counts.resize(n + 1, 0);
+            }
+            counts[n] += 1;
+        }
+    }
+    if opened == 0 {
+        return "Nothing opened".to_string();
+    }
+    let parts: Vec<String> = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(n, &count)| match NUMBER_WORDS.get(n) {
+            Some(&words) => count_words(count, words),
+            None => count_words(count, (&format!("{}", n), &format!("{}s", n))),
+        })
+        .collect();
+    let opened = count_words(opened, ("cell", "cells"));
+    format!("Opened {}: {}", opened, parts.join(", "))
+}
+
+/// The name of a column, just like spreadsheets: A, B, ..., Z, AA, AB, ...
+pub fn col_name(col: usize) -> String {
+    let mut name = vec![];
+    let mut col = col + 1;
+    while col > 0 {
+        name.push(b'A' + ((col - 1) % 26) as u8);
+        col = (col - 1) / 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+/// The name of a cell, column letters and 1-based row number, e.g. "C7",
+/// and the 1-based layer after a slash on a board of layers, e.g. "C7/2".
+pub fn cell_name(pos: usize, conf: &config::GameConfig) -> String {
+    let (layer, pos) = (pos / conf.layer_size(), pos % conf.layer_size());
+    let name = format!("{}{}", col_name(pos % conf.width), pos / conf.width + 1);
+    match conf.layers {
+        1 => name,
+        _ => format!("{}/{}", name, layer + 1),
+    }
+}
+
+/// Parse a cell name like "C7" or "C7/2" (case insensitive) into a position,
+/// the first layer when it has none.
+pub fn parse_cell_name(name: &str, conf: &config::GameConfig) -> Option<usize> {
+    let (name, layer) = match name.split_once('/') {
+        Some((name, layer)) => (name, layer.trim().parse::<usize>().ok()?.checked_sub(1)?),
+        None => (name, 0),
+    };
+    if layer >= conf.layers {
+        return None;
+    }
+    let name = name.trim().to_ascii_uppercase();
+    let split = name.find(|c: char| !c.is_ascii_uppercase())?;
+    let (letters, digits) = name.split_at(split);
+    if letters.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let col = letters.bytes().try_fold(0usize, |acc, b| {
+        acc.checked_mul(26)?.checked_add((b - b'A' + 1) as usize)
+    })? - 1;
+    let row = digits.parse::<usize>().ok()?.checked_sub(1)?;
+    if col >= conf.width || row >= conf.height {
+        return None;
+    }
+    Some(layer * conf.layer_size() + row * conf.width + col)
+}
+
+/// Move from pos by (dx, dy) columns and rows on its layer, stop at the edges or wrap on a torus.
+/// On a hex board a row up or down goes to one of the two cells on that side, the left one or the
+/// right one by dx, or the one in the same column without dx, so it zigzags between neighbours.
+pub fn step(conf: &config::GameConfig, pos: usize, dx: isize, dy: isize) -> usize {
+    let (h, w) = (conf.height as isize, conf.width as isize);
+    let base = pos - pos % conf.layer_size();
+    let pos = (pos - base) as isize;
+    let (row, col) = match conf.topology {
+        config::Topology::Hex if dy != 0 => {
+            let dirs = if pos / w % 2 == 0 {
+                &HEX_EVEN_DIRS
+            } else {
+                &HEX_ODD_DIRS
+            };
+            let side: Vec<isize> = dirs
+                .iter()
+                .filter(|&&(row, _)| row == dy.signum())
+                .map(|&(_, col)| col)
+                .collect();
+            let dc = match dx.signum() {
+                -1 => side[0],
+                1 => side[1],
+                _ => 0,
+            };
+            let (row, col) = (pos / w + dy.signum(), pos % w + dc);
+            if !(0..h).contains(&row) || !(0..w).contains(&col) {
+                return base + pos as usize;
+            }
+            (row, col)
+        }
+        config::Topology::Torus => ((pos / w + dy).rem_euclid(h), (pos % w + dx).rem_euclid(w)),
+        _ => (
+            (pos / w + dy).clamp(0, h - 1),
+            (pos % w + dx).clamp(0, w - 1),
+        ),
+    };
+    base + (row * w + col) as usize
+}
+
+/// Move from pos to the same cell `dz` layers further, stop at the first and the last layer.
+pub fn step_layer(conf: &config::GameConfig, pos: usize, dz: isize) -> usize {
+    let size = conf.layer_size();
+    let layer = (pos / size) as isize + dz;
+    layer.clamp(0, conf.layers as isize - 1) as usize * size + pos % size
+}
+
 #[rustfmt::skip]
 const DIRS: [(isize, isize); 8] = [
     (-1, -1), (-1, 0), (-1, 1),
@@ -113,73 +317,226 @@ const DIRS: [(isize, isize); 8] = [
     (1, -1), (1, 0), (1, 1),
 ];
 
-/// check surround cells of pos, and execute fn(pos) for them
-fn check_around_fn(h: usize, w: usize, pos: usize, mut op: impl FnMut(usize)) {
-    DIRS.iter().for_each(|(dx, dy)| {
-        let pos = pos as isize;
-        let (h, w) = (h as isize, w as isize);
-        let (a, b) = (pos / w + dx, pos % w + dy);
-        if a >= 0 && a < h && b >= 0 && b < w {
-            op((a * w + b) as usize)
+#[rustfmt::skip]
+const CROSS_DIRS: [(isize, isize); 4] = [
+             (-1, 0),
+    (0, -1),          (0, 1),
+             (1, 0),
+];
+
+#[rustfmt::skip]
+const KNIGHT_DIRS: [(isize, isize); 8] = [
+    (-2, -1), (-2, 1),
+    (-1, -2), (-1, 2),
+    (1, -2), (1, 2),
+    (2, -1), (2, 1),
+];
+
+#[rustfmt::skip]
+const RING_DIRS: [(isize, isize); 16] = [
+    (-2, -2), (-2, -1), (-2, 0), (-2, 1), (-2, 2),
+    (-1, -2),                             (-1, 2),
+    (0, -2),                              (0, 2),
+    (1, -2),                              (1, 2),
+    (2, -2), (2, -1), (2, 0), (2, 1), (2, 2),
+];
+
+/// The neighbours of a hex cell on an even row, the odd rows sit half a cell to the right.
+#[rustfmt::skip]
+const HEX_EVEN_DIRS: [(isize, isize); 6] = [
+    (-1, -1), (-1, 0),
+    (0, -1),           (0, 1),
+    (1, -1), (1, 0),
+];
+
+#[rustfmt::skip]
+const HEX_ODD_DIRS: [(isize, isize); 6] = [
+    (-1, 0), (-1, 1),
+    (0, -1),          (0, 1),
+    (1, 0), (1, 1),
+];
+
+/// check surround cells of pos, and execute fn(pos) for them, the neighbours follow the rule of conf.
+/// On a torus the edges wrap, and a cell touched twice across a small board counts once.
+/// With layers, the cells right above and below and their neighbours by the rule count too,
+/// 26 in all for the usual rule, the layers don't wrap.
+pub fn check_around_fn(conf: &config::GameConfig, pos: usize, mut op: impl FnMut(usize)) {
+    let (h, w) = (conf.height as isize, conf.width as isize);
+    let (layer, pos) = (pos / conf.layer_size(), pos % conf.layer_size());
+    let (row, col) = (pos as isize / w, pos as isize % w);
+    let dirs: &[(isize, isize)] = match (conf.topology, &conf.neighbours) {
+        (config::Topology::Hex, _) if row % 2 == 0 => &HEX_EVEN_DIRS,
+        (config::Topology::Hex, _) => &HEX_ODD_DIRS,
+        (_, config::Neighbours::King) => &DIRS,
+        (_, config::Neighbours::Cross) => &CROSS_DIRS,
+        (_, config::Neighbours::Knight) => &KNIGHT_DIRS,
+        (_, config::Neighbours::Ring) => &RING_DIRS,
+        (_, config::Neighbours::Custom(offsets)) => offsets,
+    };
+    let here = layer * conf.layer_size();
+    let mut seen = vec![here + pos];
+    // Its own layer first, in the order of the rule.
+    for dz in [0, -1, 1] {
+        let next = layer as isize + dz;
+        if next < 0 || next >= conf.layers as isize {
+            continue;
         }
-    });
+        let base = next as usize * conf.layer_size();
+        let straight = (dz != 0).then_some(&(0, 0));
+        for (dx, dy) in straight.into_iter().chain(dirs) {
+            let (a, b) = (row + dx, col + dy);
+            let around = base
+                + match conf.topology {
+                    config::Topology::Torus => a.rem_euclid(h) * w + b.rem_euclid(w),
+                    _ if a >= 0 && a < h && b >= 0 && b < w => a * w + b,
+                    _ => continue,
+                } as usize;
+            if !seen.contains(&around) {
+                seen.push(around);
+                op(around);
+            }
+        }
+    }
 }
 
-fn binary_random(rng: &mut ThreadRng, bomb: usize, map: &mut [i8]) {
+fn binary_random(rng: &mut impl Rng, bomb: usize, map: &mut [bool]) {
     let len = map.len();
     if bomb <= 1 {
         let rand_pos = rng.gen_range(0..len);
-        map[rand_pos] = Content::Bomb as i8;
+        map[rand_pos] = true;
         return;
     }
     let mid = len / 2;
     binary_random(rng, bomb / 2, &mut map[..mid]);
-    binary_random(rng, (bomb + 1) / 2, &mut map[mid..]);
+    binary_random(rng, bomb.div_ceil(2), &mut map[mid..]);
+}
+
+/// The ways to put k mines on n cells, at most `stack` a cell, as ln(ways) for n in 0..=cells and k in 0..=mines.
+/// With one mine a cell it's ln(C(n, k)). Impossible counts are -inf.
+pub fn ln_ways(cells: usize, mines: usize, stack: usize) -> Vec<Vec<f64>> {
+    let mut ways = vec![vec![f64::NEG_INFINITY; mines + 1]; cells + 1];
+    ways[0][0] = 0.0;
+    for n in 1..=cells {
+        for k in 0..=mines {
+            let terms: Vec<f64> = (0..=stack.min(k)).map(|c| ways[n - 1][k - c]).collect();
+            let top = terms.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            if top > f64::NEG_INFINITY {
+                ways[n][k] = top + terms.iter().map(|t| (t - top).exp()).sum::<f64>().ln();
+            }
+        }
+    }
+    ways
+}
+
+/// Put the mines with at most `stack` a cell, every way of doing it as likely, none on `init_pos`.
+fn stacked_random(rng: &mut impl Rng, conf: &config::GameConfig, init_pos: usize) -> Vec<u8> {
+    let cells: Vec<usize> = (0..conf.get_size())
+        .filter(|&pos| pos != init_pos)
+        .collect();
+    let ways = ln_ways(cells.len(), conf.bomb, conf.stack);
+    let mut counts = vec![0u8; conf.get_size()];
+    let mut left = conf.bomb;
+    for (i, &pos) in cells.iter().enumerate() {
+        let rest = cells.len() - i - 1;
+        // The chance of c mines here is the share of the ways left for the other cells.
+        let mut pick = rng.gen::<f64>();
+        for c in 0..=conf.stack.min(left) {
+            let p = (ways[rest][left - c] - ways[rest + 1][left]).exp();
+            if pick < p || c == conf.stack.min(left) {
+                counts[pos] = c as u8;
+                left -= c;
+                break;
+            }
+            pick -= p;
+        }
+    }
+    counts
 }
 
 /// Generate a random map with a specific position and game configs.
-pub fn gen_map(
-    init_at: &draw::Pos,
-    now_at: &draw::Pos,
-    conf: &config::GameConfig,
-    rng: &mut ThreadRng,
-) -> Vec<Cell> {
-    let init_pos = (now_at.1 - init_at.1) as usize * conf.width + (now_at.0 - init_at.0) as usize;
-    let mut map = vec![0i8; conf.get_size()];
-    binary_random(rng, conf.bomb, &mut map);
+pub fn gen_map(init_pos: usize, conf: &config::GameConfig, rng: &mut impl Rng) -> Vec<Cell> {
+    if conf.stack > 1 {
+        return from_counts(conf, &stacked_random(rng, conf, init_pos));
+    }
+    let mut mines = vec![false; conf.get_size()];
+    binary_random(rng, conf.bomb, &mut mines);
 
-    if map[init_pos] == Content::Bomb as i8 {
-        let idx = map.iter().position(|&x| x != Content::Bomb as i8).unwrap();
-        map.swap(init_pos, idx);
+    if mines[init_pos] {
+        let idx = mines.iter().position(|&mine| !mine).unwrap();
+        mines.swap(init_pos, idx);
     }
+    from_mines(conf, &mines)
+}
 
-    let mut map_clone = vec![0i8; map.len()];
-    map.iter()
-        .enumerate()
-        .filter(|(_, &val)| val == Content::Bomb as i8)
-        .for_each(|(idx, &val)| {
-            map_clone[idx] = val;
-            check_around_fn(conf.height, conf.width, idx, |pos| {
-                if map[pos] != Content::Bomb as i8 {
-                    map_clone[pos] += 1;
+/// Make a covered map with mines on the given positions.
+pub fn from_mines(conf: &config::GameConfig, mines: &[bool]) -> Vec<Cell> {
+    let counts: Vec<u8> = mines.iter().map(|&mine| mine as u8).collect();
+    from_counts(conf, &counts)
+}
+
+/// Make a covered map with the given number of mines on each position.
+pub fn from_counts(conf: &config::GameConfig, counts: &[u8]) -> Vec<Cell> {
+    // A number counts the mines among its own neighbours, which matters for a rule one-sided like "1,0".
+    (0..counts.len())
+        .map(|idx| {
+            let mut around = 0u8;
+            check_around_fn(conf, idx, |pos| around = around.saturating_add(counts[pos]));
+            let content = if counts[idx] > 0 {
+                Content::Bomb(counts[idx])
+            } else {
+                Content::Number(around)
+            };
+            Cell::new(content, Surface::Cover)
+        })
+        .collect()
+}
+
+/// The cells with mines, the ones left covered when a game is won.
+pub fn mine_cells(map: &[Cell]) -> usize {
+    map.iter().filter(|cell| cell.content.is_mine()).count()
+}
+
+/// The 3BV of a map, the fewest clicks to open every safe cell:
+/// one for each opening of blanks, and one for each number not touching a blank.
+pub fn three_bv(conf: &config::GameConfig, map: &[Cell]) -> usize {
+    let mut seen = vec![false; map.len()];
+    let mut clicks = 0;
+    for (pos, cell) in map.iter().enumerate() {
+        if seen[pos] || cell.content != Content::Number(0) {
+            continue;
+        }
+        clicks += 1;
+        let mut queue: Queue<usize> = queue![];
+        queue.add(pos).unwrap();
+        seen[pos] = true;
+        while queue.size() > 0 {
+            let one = queue.remove().unwrap();
+            if map[one].content != Content::Number(0) {
+                continue;
+            }
+            check_around_fn(conf, one, |pos| {
+                if !seen[pos] {
+                    seen[pos] = true;
+                    queue.add(pos).unwrap();
                 }
             });
-        });
-    map_clone
-        .into_iter()
-        .map(|v| Cell::new(Content::from_i8(v), Surface::Cover))
-        .collect()
+        }
+    }
+    let lonely = map
+        .iter()
+        .zip(&seen)
+        .filter(|(cell, &seen)| !seen && !cell.content.is_mine())
+        .count();
+    clicks + lonely
 }
 
 /// Open one cell in a specific position.
 pub fn mine_map(
-    init_at: &draw::Pos,
-    now_at: &draw::Pos,
+    pos: usize,
     conf: &config::GameConfig,
     mut map: Vec<Cell>,
     left_cover: &mut usize,
 ) -> Vec<Cell> {
-    let pos = (now_at.1 - init_at.1) as usize * conf.width + (now_at.0 - init_at.0) as usize;
     let mut queue: Queue<usize> = queue![];
     queue.add(pos).unwrap();
 
@@ -188,33 +545,63 @@ pub fn mine_map(
 
         map[one].surface = match map[one].surface {
             Surface::Cover => {
-                match map[one].content {
-                    Content::Zero => check_around_fn(conf.height, conf.width, one, |pos| {
+                if let Content::Number(0) = map[one].content {
+                    check_around_fn(conf, one, |pos| {
                         if let Surface::Cover = &map[pos].surface {
                             queue.add(pos).unwrap();
                         }
-                    }),
-                    _ => (),
-                };
+                    })
+                }
                 *left_cover -= 1;
                 Surface::Open
             }
-            Surface::Open => Surface::Open,
-            Surface::Flag => Surface::Flag,
+            ref surface => surface.clone(),
         };
     }
     map
 }
 
-/// Put a flag on specific position.
+/// Open all the covered cells around an opened number in a specific position,
+/// if the flags around it stand for as many mines as the number.
+pub fn chord_map(
+    pos: usize,
+    conf: &config::GameConfig,
+    mut map: Vec<Cell>,
+    left_cover: &mut usize,
+) -> Vec<Cell> {
+    if map[pos].surface != Surface::Open {
+        return map;
+    }
+    let mut flags = 0;
+    let mut covers = vec![];
+    check_around_fn(conf, pos, |pos| match map[pos].surface {
+        Surface::Flag(n) => flags += n as usize,
+        Surface::Cover => covers.push(pos),
+        Surface::Open => (),
+    });
+    if flags != map[pos].content.count() {
+        return map;
+    }
+    for pos in covers {
+        map = mine_map(pos, conf, map, left_cover);
+    }
+    map
+}
+
+/// Whether a bomb has been opened.
+pub fn is_exploded(map: &[Cell]) -> bool {
+    map.iter()
+        .any(|cell| cell.surface == Surface::Open && cell.content.is_mine())
+}
+
+/// Put a flag on specific position, or one more where cells hold several mines.
+/// A cell flagged as full, or with no flag left to put, is covered again.
 pub fn flag_map(
-    init_at: &draw::Pos,
-    now_at: &draw::Pos,
+    pos: usize,
     conf: &config::GameConfig,
     mut map: Vec<Cell>,
     flag_num: &mut usize,
 ) -> (Vec<Cell>, bool) {
-    let pos = (now_at.1 - init_at.1) as usize * conf.width + (now_at.0 - init_at.0) as usize;
     let pre_status = map[pos].surface.clone();
 
     map[pos].surface = match map[pos].surface {
@@ -224,16 +611,276 @@ pub fn flag_map(
                 return (map, false);
             }
             *flag_num += 1;
-            Surface::Flag
+            Surface::Flag(1)
+        }
+        Surface::Flag(n) if (n as usize) < conf.stack && *flag_num < conf.bomb => {
+            *flag_num += 1;
+            Surface::Flag(n + 1)
         }
-        Surface::Flag => {
-            if *flag_num == 0 {
+        Surface::Flag(n) => {
+            if *flag_num < n as usize {
                 return (map, false);
             }
-            *flag_num -= 1;
+            *flag_num -= n as usize;
             Surface::Cover
         }
     };
     let now_status = map[pos].surface.clone();
     (map, pre_status != now_status)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        cell_name, check_around_fn, col_name, flag_map, from_counts, from_mines, parse_cell_name,
+        step, step_layer, three_bv, Content, Surface,
+    };
+    use crate::config::{GameConfig, GameMode, Neighbours, Topology};
+
+    #[test]
+    fn name_columns() {
+        assert_eq!("A", col_name(0));
+        assert_eq!("Z", col_name(25));
+        assert_eq!("AA", col_name(26));
+        assert_eq!("AD", col_name(29));
+    }
+
+    #[test]
+    fn name_cells() {
+        let conf = GameConfig::from_game_mode(GameMode::Hard);
+        assert_eq!("C7", cell_name(6 * 30 + 2, &conf));
+        assert_eq!(Some(6 * 30 + 2), parse_cell_name("c7", &conf));
+        assert_eq!(Some(15 * 30 + 29), parse_cell_name("AD16", &conf));
+        assert_eq!(None, parse_cell_name("AE1", &conf));
+        assert_eq!(None, parse_cell_name("A17", &conf));
+        assert_eq!(None, parse_cell_name("7C", &conf));
+    }
+
+    #[test]
+    fn count_three_bv() {
+        let conf = GameConfig::sized(3, 4, 2);
+        let mut mines = vec![false; 12];
+        mines[0] = true;
+        mines[11] = true;
+        // Two openings cover every number.
+        assert_eq!(2, three_bv(&conf, &from_mines(&conf, &mines)));
+
+        let conf = GameConfig::sized(1, 3, 2);
+        assert_eq!(1, three_bv(&conf, &from_mines(&conf, &[true, false, true])));
+    }
+
+    #[test]
+    fn torus_wraps() {
+        let conf = GameConfig {
+            topology: Topology::Torus,
+            ..GameConfig::sized(4, 4, 1)
+        };
+        let mut mines = vec![false; 16];
+        mines[0] = true;
+        let map = from_mines(&conf, &mines);
+        // The far corner touches the mine across both edges.
+        assert!(matches!(map[15].content, Content::Number(1)));
+        assert!(matches!(map[10].content, Content::Number(0)));
+        assert_eq!(15, step(&conf, 0, -1, -1));
+        assert_eq!(3, step(&conf, 0, -1, 0));
+
+        // On a 2x2 torus each cell touches the other three once.
+        let conf = GameConfig {
+            topology: Topology::Torus,
+            ..GameConfig::sized(2, 2, 3)
+        };
+        let map = from_mines(&conf, &[true, true, true, false]);
+        assert!(matches!(map[3].content, Content::Number(3)));
+    }
+
+    #[test]
+    fn hex_neighbours() {
+        let conf = GameConfig {
+            topology: Topology::Hex,
+            ..GameConfig::sized(3, 3, 1)
+        };
+        let around = |pos| {
+            let mut cells = vec![];
+            check_around_fn(&conf, pos, |p| cells.push(p));
+            cells
+        };
+        // The middle row is odd, half a cell to the right.
+        assert_eq!(vec![1, 2, 3, 5, 7, 8], around(4));
+        assert_eq!(vec![1, 3], around(0));
+        assert_eq!(vec![0, 2, 3, 4], around(1));
+    }
+
+    #[test]
+    fn hex_steps() {
+        let conf = GameConfig {
+            topology: Topology::Hex,
+            ..GameConfig::sized(4, 4, 1)
+        };
+        for pos in 0..conf.get_size() {
+            let mut around = vec![];
+            check_around_fn(&conf, pos, |p| around.push(p));
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let to = step(&conf, pos, dx, dy);
+                    assert!(
+                        to == pos || around.contains(&to),
+                        "{} by {},{}",
+                        pos,
+                        dx,
+                        dy
+                    );
+                }
+            }
+        }
+        // An even row sits to the left, its right neighbours keep the column.
+        assert_eq!(5, step(&conf, 9, 0, -1));
+        assert_eq!(4, step(&conf, 9, -1, -1));
+        assert_eq!(13, step(&conf, 9, 1, 1));
+        assert_eq!(10, step(&conf, 5, 1, 1));
+    }
+
+    #[test]
+    fn neighbour_rules() {
+        let conf = GameConfig {
+            neighbours: Neighbours::Knight,
+            ..GameConfig::sized(3, 3, 1)
+        };
+        let mut mines = vec![false; 9];
+        mines[0] = true;
+        let map = from_mines(&conf, &mines);
+        assert!(matches!(map[5].content, Content::Number(1)));
+        assert!(matches!(map[7].content, Content::Number(1)));
+        assert!(matches!(map[4].content, Content::Number(0)));
+
+        // A one-sided rule: each number counts the mine below it only.
+        let conf = GameConfig {
+            neighbours: Neighbours::from_name("1,0").unwrap(),
+            ..GameConfig::sized(3, 1, 1)
+        };
+        let map = from_mines(&conf, &[false, true, false]);
+        assert!(matches!(map[0].content, Content::Number(1)));
+        assert!(matches!(map[2].content, Content::Number(0)));
+
+        assert_eq!(None, Neighbours::from_name("0,0"));
+        assert_eq!(None, Neighbours::from_name("1,0 1,0"));
+        assert_eq!(None, Neighbours::from_name(""));
+        // Any number of offsets, each cell counted once.
+        let far: Vec<String> = (1..=30).map(|col| format!("0,{}", col)).collect();
+        let conf = GameConfig {
+            height: 1,
+            width: 31,
+            neighbours: Neighbours::from_name(&far.join(" ")).unwrap(),
+            ..conf
+        };
+        let mut count = 0;
+        check_around_fn(&conf, 0, |_| count += 1);
+        assert_eq!(30, count);
+
+        // The ring is the sixteen cells between the 5x5 and the 3x3 squares.
+        let conf = GameConfig {
+            height: 5,
+            width: 5,
+            neighbours: Neighbours::Ring,
+            ..conf
+        };
+        let mut around = vec![];
+        check_around_fn(&conf, 12, |p| around.push(p));
+        around.sort();
+        let ring: Vec<usize> = (0..25)
+            .filter(|p| p / 5 % 4 == 0 || p % 5 % 4 == 0)
+            .collect();
+        assert_eq!(ring, around);
+    }
+
+    #[test]
+    fn stacked_cells() {
+        let conf = GameConfig {
+            stack: 3,
+            ..GameConfig::sized(1, 3, 3)
+        };
+        let map = from_counts(&conf, &[2, 0, 1]);
+        assert_eq!(Content::Bomb(2), map[0].content);
+        assert_eq!(Content::Number(3), map[1].content);
+
+        // Flags go up to the stack, then the cell is covered again.
+        let mut flags = 0;
+        let mut map = map;
+        for surface in [
+            Surface::Flag(1),
+            Surface::Flag(2),
+            Surface::Flag(3),
+            Surface::Cover,
+        ] {
+            map = flag_map(0, &conf, map, &mut flags).0;
+            assert_eq!(surface, map[0].surface);
+        }
+        assert_eq!(0, flags);
+
+        // No flag left to add, so it comes off.
+        flags = 2;
+        map = flag_map(0, &conf, map, &mut flags).0;
+        map = flag_map(0, &conf, map, &mut flags).0;
+        assert_eq!(Surface::Cover, map[0].surface);
+        assert_eq!(2, flags);
+    }
+
+    #[test]
+    fn most_around() {
+        for topology in [Topology::Square, Topology::Hex] {
+            for neighbours in [
+                Neighbours::King,
+                Neighbours::Cross,
+                Neighbours::Knight,
+                Neighbours::Ring,
+                Neighbours::from_name("1,0 0,3 -2,2").unwrap(),
+            ] {
+                for layers in 1..=3 {
+                    let conf = GameConfig {
+                        topology,
+                        neighbours: neighbours.clone(),
+                        layers,
+                        ..GameConfig::from_game_mode(GameMode::Simple)
+                    };
+                    let mut count = 0;
+                    let middle = conf.layer_size() * (layers / 2) + conf.layer_size() / 2;
+                    check_around_fn(&conf, middle, |_| count += 1);
+                    assert_eq!(conf.most_around(), count, "{:?}", conf);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn layers() {
+        let conf = GameConfig {
+            layers: 3,
+            ..GameConfig::sized(3, 3, 1)
+        };
+        let count = |pos| {
+            let mut count = 0;
+            check_around_fn(&conf, pos, |_| count += 1);
+            count
+        };
+        assert_eq!(26, count(13));
+        assert_eq!(7, count(0));
+        assert_eq!(17, count(4));
+
+        // A mine in the middle touches every other cell.
+        let mut mines = vec![false; 27];
+        mines[13] = true;
+        let map = from_mines(&conf, &mines);
+        assert!(map
+            .iter()
+            .all(|cell| cell.content == Content::Number(1) || cell.content.is_mine()));
+
+        assert_eq!("B2/2", cell_name(13, &conf));
+        assert_eq!(Some(13), parse_cell_name("b2/2", &conf));
+        assert_eq!(Some(4), parse_cell_name("B2", &conf));
+        assert_eq!(None, parse_cell_name("B2/4", &conf));
+        // Moves stay on their layer, and stop at the first and the last one.
+        assert_eq!(20, step(&conf, 18, 2, 0));
+        assert_eq!(22, step_layer(&conf, 13, 1));
+        assert_eq!(22, step_layer(&conf, 22, 1));
+        assert_eq!(4, step_layer(&conf, 22, -5));
+    }
+}
@@ -0,0 +1,380 @@
+//! Name the patterns people use to find safe cells and mines, e.g. 1-2-1.
+//!
+//! Like the solver, flags are never trusted. What one pattern proves is known
+//! to the next, so a cell deep in the board is explained by the last link of a chain.
+
+use std::fmt;
+
+use crate::{config, map};
+
+/// The patterns, from the simplest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// A number with all its mines found, or with as many unknown cells as mines left.
+    Basic,
+    /// Two 1s where one sees all the cells of the other, usually along an edge.
+    OneOne,
+    /// A 2 between two 1s in a line.
+    OneTwoOne,
+    /// Two 2s between two 1s in a line.
+    OneTwoTwoOne,
+    /// Two numbers sharing some cells, the rest of them tell which.
+    Shared,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Basic => write!(f, "basic subtraction"),
+            Pattern::OneOne => write!(f, "1-1 edge"),
+            Pattern::OneTwoOne => write!(f, "1-2-1"),
+            Pattern::OneTwoTwoOne => write!(f, "1-2-2-1"),
+            Pattern::Shared => write!(f, "shared region"),
+        }
+    }
+}
+
+/// What one pattern proves.
+#[derive(Debug)]
+pub struct Deduction {
+    pub pattern: Pattern,
+    /// The opened numbers the pattern reads.
+    pub numbers: Vec<usize>,
+    pub safe: Vec<usize>,
+    pub mines: Vec<usize>,
+}
+
+impl Deduction {
+    fn proves(&self, pos: usize) -> bool {
+        self.safe.contains(&pos) || self.mines.contains(&pos)
+    }
+}
+
+/// Cells already proved, `Some(true)` for mines.
+/// Where cells hold several mines, a proved mine is a cell as full as can be.
+type Known = Vec<Option<bool>>;
+
+/// An opened number: `mines` mines not proved yet hide among these unknown `cells`.
+struct Number {
+    pos: usize,
+    mines: usize,
+    cells: Vec<usize>,
+    /// The most mines a cell holds.
+    full: usize,
+}
+
+/// All the numbers next to unknown cells, indexed by position too.
+fn numbers(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    known: &Known,
+) -> (Vec<Number>, Vec<Option<usize>>) {
+    let mut numbers = vec![];
+    let mut index = vec![None; map.len()];
+    for (pos, cell) in map.iter().enumerate() {
+        if cell.surface != map::Surface::Open || cell.content.is_mine() {
+            continue;
+        }
+        let (mut cells, mut found) = (vec![], 0);
+        map::check_around_fn(conf, pos, |pos| match (&map[pos].surface, known[pos]) {
+            (map::Surface::Open, _) => (),
+            (_, Some(true)) => found += conf.stack,
+            (_, Some(false)) => (),
+            (_, None) => cells.push(pos),
+        });
+        if !cells.is_empty() {
+            cells.sort_unstable();
+            let mines = cell.content.count().saturating_sub(found);
+            index[pos] = Some(numbers.len());
+            numbers.push(Number {
+                pos,
+                mines,
+                cells,
+                full: conf.stack,
+            });
+        }
+    }
+    (numbers, index)
+}
+
+/// What one number proves alone, as (safe, mines).
+fn single(number: &Number) -> Option<(Vec<usize>, Vec<usize>)> {
+    if number.mines == 0 {
+        Some((number.cells.clone(), vec![]))
+    } else if number.mines == number.cells.len() * number.full {
+        Some((vec![], number.cells.clone()))
+    } else {
+        None
+    }
+}
+
+/// What `b` proves with the help of `a`, as (safe, mines).
+///
+/// When `b` needs `a`'s mines all in the shared cells, the cells only `a` sees are safe,
+/// and the cells only `b` sees are mines.
+fn pair(a: &Number, b: &Number) -> Option<(Vec<usize>, Vec<usize>)> {
+    let only_a: Vec<usize> = a
+        .cells
+        .iter()
+        .filter(|c| !b.cells.contains(c))
+        .copied()
+        .collect();
+    let only_b: Vec<usize> = b
+        .cells
+        .iter()
+        .filter(|c| !a.cells.contains(c))
+        .copied()
+        .collect();
+    let shared = a.cells.len() - only_a.len();
+    if shared == 0 || (only_a.is_empty() && only_b.is_empty()) {
+        return None;
+    }
+    if b.mines >= a.mines && b.mines - a.mines == only_b.len() * b.full {
+        Some((only_a, only_b))
+    } else {
+        None
+    }
+}
+
+/// The other numbers sharing cells with `number`, from the numbers seeing each cell.
+/// It works for any rule of neighbours, even one-sided ones.
+fn nearby(numbers: &[Number], seen_by: &[Vec<usize>], number: &Number) -> Vec<usize> {
+    let mut near: Vec<usize> = number
+        .cells
+        .iter()
+        .flat_map(|&cell| seen_by[cell].iter().copied())
+        .collect();
+    near.sort_unstable();
+    near.dedup();
+    near.into_iter()
+        .filter(|&idx| numbers[idx].pos != number.pos)
+        .collect()
+}
+
+/// Name the pattern `a` and `b` are part of, with all the numbers in it.
+fn classify(
+    conf: &config::GameConfig,
+    numbers: &[Number],
+    index: &[Option<usize>],
+    a: &Number,
+    b: &Number,
+) -> (Pattern, Vec<usize>) {
+    let (ar, ac) = ((a.pos / conf.width) as isize, (a.pos % conf.width) as isize);
+    let (dr, dc) = (
+        (b.pos / conf.width) as isize - ar,
+        (b.pos % conf.width) as isize - ac,
+    );
+    // Lines only mean something with the eight cells around on a flat square grid.
+    let lines = conf.topology != config::Topology::Hex
+        && conf.neighbours == config::Neighbours::King
+        && conf.stack == 1
+        && conf.layers == 1;
+    if lines && dr.abs() + dc.abs() == 1 {
+        // The number k steps from a, along the line from a to b.
+        let along = |k: isize| {
+            let (r, c) = (ar + dr * k, ac + dc * k);
+            if r < 0 || c < 0 || r >= conf.height as isize || c >= conf.width as isize {
+                return None;
+            }
+            index[r as usize * conf.width + c as usize].map(|idx| &numbers[idx])
+        };
+        let mines = |k| along(k).map(|n: &Number| n.mines);
+        let line = |len| (0..len).filter_map(|k| along(k).map(|n| n.pos)).collect();
+        match (a.mines, b.mines, mines(2), mines(3)) {
+            (1, 2, Some(1), _) => return (Pattern::OneTwoOne, line(3)),
+            (1, 2, Some(2), Some(1)) => return (Pattern::OneTwoTwoOne, line(4)),
+            (1, 1, _, _) => return (Pattern::OneOne, line(2)),
+            _ => (),
+        }
+    }
+    (Pattern::Shared, vec![a.pos, b.pos])
+}
+
+/// Everything `positions` prove together, as (safe, mines), from what is known.
+fn close(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    known: &Known,
+    positions: &[usize],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut local = known.clone();
+    loop {
+        let (numbers, _) = numbers(conf, map, &local);
+        let numbers: Vec<&Number> = numbers
+            .iter()
+            .filter(|n| positions.contains(&n.pos))
+            .collect();
+        let mut proved = vec![];
+        for a in &numbers {
+            proved.extend(single(a));
+            for b in &numbers {
+                proved.extend(pair(a, b));
+            }
+        }
+        if proved.is_empty() {
+            break;
+        }
+        for (safe, mines) in proved {
+            safe.into_iter().for_each(|pos| local[pos] = Some(false));
+            mines.into_iter().for_each(|pos| local[pos] = Some(true));
+        }
+    }
+    let new = |mine| {
+        (0..map.len())
+            .filter(|&pos| known[pos].is_none() && local[pos] == Some(mine))
+            .collect()
+    };
+    (new(false), new(true))
+}
+
+/// All the patterns to be seen on the board now, the basic ones alone if any.
+fn step(conf: &config::GameConfig, map: &[map::Cell], known: &Known) -> Vec<Deduction> {
+    let (numbers, index) = numbers(conf, map, known);
+    let basic: Vec<Deduction> = numbers
+        .iter()
+        .filter_map(|n| {
+            single(n).map(|(safe, mines)| Deduction {
+                pattern: Pattern::Basic,
+                numbers: vec![n.pos],
+                safe,
+                mines,
+            })
+        })
+        .collect();
+    if !basic.is_empty() {
+        return basic;
+    }
+    let mut seen_by = vec![vec![]; map.len()];
+    for (idx, number) in numbers.iter().enumerate() {
+        number
+            .cells
+            .iter()
+            .for_each(|&cell| seen_by[cell].push(idx));
+    }
+    let mut found = vec![];
+    for b in &numbers {
+        for a in nearby(&numbers, &seen_by, b)
+            .into_iter()
+            .map(|idx| &numbers[idx])
+        {
+            if pair(a, b).is_none() {
+                continue;
+            }
+            let (pattern, involved) = classify(conf, &numbers, &index, a, b);
+            let (safe, mines) = close(conf, map, known, &involved);
+            found.push(Deduction {
+                pattern,
+                numbers: involved,
+                safe,
+                mines,
+            });
+        }
+    }
+    found
+}
+
+/// The patterns to be seen on the board now.
+pub fn find(conf: &config::GameConfig, map: &[map::Cell]) -> Vec<Deduction> {
+    step(conf, map, &vec![None; map.len()])
+}
+
+/// Go round after round, each knowing what the rounds before proved,
+/// until a deduction is picked by `until`.
+fn rounds(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    until: impl Fn(&Deduction) -> bool,
+) -> Option<Deduction> {
+    let mut known = vec![None; map.len()];
+    loop {
+        let mut found = step(conf, map, &known);
+        if let Some(idx) = found.iter().position(&until) {
+            return Some(found.swap_remove(idx));
+        }
+        if found.is_empty() {
+            return None;
+        }
+        for d in found {
+            d.safe.iter().for_each(|&pos| known[pos] = Some(false));
+            d.mines.iter().for_each(|&pos| known[pos] = Some(true));
+        }
+    }
+}
+
+/// The pattern that proves the cell at pos safe or a mine, if patterns are enough.
+pub fn explain(conf: &config::GameConfig, map: &[map::Cell], pos: usize) -> Option<Deduction> {
+    if map[pos].surface == map::Surface::Open {
+        return None;
+    }
+    rounds(conf, map, |d| d.proves(pos))
+}
+
+/// The first pattern proving some cells safe, if patterns are enough.
+pub fn next_safe(conf: &config::GameConfig, map: &[map::Cell]) -> Option<Deduction> {
+    rounds(conf, map, |d| !d.safe.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{explain, Pattern};
+    use crate::config::GameConfig;
+    use crate::map::{self, Surface};
+
+    /// A map from rows of '*' (mine) and '.' (safe), opening the cells marked in `open`.
+    fn board(rows: &[&str], open: &[&str]) -> (GameConfig, Vec<map::Cell>) {
+        let conf = GameConfig::sized(
+            rows.len(),
+            rows[0].len(),
+            rows.iter().map(|r| r.matches('*').count()).sum(),
+        );
+        let mines: Vec<bool> = rows
+            .iter()
+            .flat_map(|r| r.chars().map(|c| c == '*'))
+            .collect();
+        let mut map = map::from_mines(&conf, &mines);
+        for (pos, c) in open.iter().flat_map(|r| r.chars()).enumerate() {
+            if c == 'o' {
+                map[pos].surface = Surface::Open;
+            }
+        }
+        (conf, map)
+    }
+
+    #[test]
+    fn basic() {
+        let (conf, map) = board(&["*..", "..."], &["...", "ooo"]);
+        let d = explain(&conf, &map, 1).unwrap();
+        assert_eq!(Pattern::Basic, d.pattern);
+        assert_eq!(vec![5], d.numbers);
+        assert!(d.safe.contains(&1) && d.safe.contains(&2));
+    }
+
+    #[test]
+    fn one_one() {
+        // 1 1 along the left wall: the cell above the third column is safe.
+        let (conf, map) = board(&[".*.*", "....", "...."], &["....", "oooo", "oooo"]);
+        let d = explain(&conf, &map, 2).unwrap();
+        assert_eq!(Pattern::OneOne, d.pattern);
+        assert_eq!(vec![5, 4], d.numbers);
+        assert_eq!(vec![2], d.safe);
+    }
+
+    #[test]
+    fn one_two_one() {
+        let (conf, map) = board(&["*.*", "...", "..."], &["...", "ooo", "ooo"]);
+        let d = explain(&conf, &map, 1).unwrap();
+        assert_eq!(Pattern::OneTwoOne, d.pattern);
+        assert_eq!(vec![3, 4, 5], d.numbers);
+        assert_eq!(vec![1], d.safe);
+        assert_eq!(vec![0, 2], d.mines);
+    }
+
+    #[test]
+    fn one_two_two_one() {
+        let (conf, map) = board(&[".**.", "....", "...."], &["....", "oooo", "oooo"]);
+        let d = explain(&conf, &map, 0).unwrap();
+        assert_eq!(Pattern::OneTwoTwoOne, d.pattern);
+        assert_eq!(vec![0, 3], d.safe);
+        assert_eq!(vec![1, 2], d.mines);
+    }
+}
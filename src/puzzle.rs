@@ -0,0 +1,105 @@
+//! Puzzles: positions with some cells opened already, to finish without a guess.
+//! They are scored on mistakes, not on time.
+
+use rand::Rng;
+
+use crate::{config, map, solver};
+
+/// Every covered cell the solver proves safe, all at once.
+fn proved_safe(conf: &config::GameConfig, map: &[map::Cell]) -> Vec<usize> {
+    solver::probabilities(conf, map)
+        .iter()
+        .enumerate()
+        .filter(|&(pos, p)| {
+            map[pos].surface == map::Surface::Cover && p.is_some_and(solver::is_safe)
+        })
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+/// Open the proved safe cells round after round, and keep the position before each round.
+/// Return the positions, and if every safe cell got opened without a guess.
+fn solve(conf: &config::GameConfig, map: &[map::Cell]) -> (Vec<Vec<map::Cell>>, bool) {
+    let mut map = map.to_vec();
+    let mine_cells = map::mine_cells(&map);
+    let mut left_cover = map
+        .iter()
+        .filter(|c| c.surface != map::Surface::Open)
+        .count();
+    let mut rounds = vec![];
+    while left_cover > mine_cells {
+        let safe = proved_safe(conf, &map);
+        if safe.is_empty() {
+            return (rounds, false);
+        }
+        rounds.push(map.clone());
+        for pos in safe {
+            map = map::mine_map(pos, conf, map, &mut left_cover);
+        }
+    }
+    (rounds, true)
+}
+
+/// If a position can be finished without guessing.
+pub fn solvable(conf: &config::GameConfig, map: &[map::Cell]) -> bool {
+    solve(conf, map).1
+}
+
+/// How many random boards `generate` tries, a few hundred at most are needed on a usual board.
+const TRIES: usize = 1000;
+
+/// A new puzzle: a random board that needs no guess after its first click,
+/// shown halfway through being solved. At least three rounds of deduction are left.
+/// None when no board of `TRIES` was one, as under rules where they are rare.
+pub fn generate(conf: &config::GameConfig, rng: &mut impl Rng) -> Option<Vec<map::Cell>> {
+    for _ in 0..TRIES {
+        let start = rng.gen_range(0..conf.get_size());
+        let mut left_cover = conf.get_size();
+        let map = map::mine_map(start, conf, map::gen_map(start, conf, rng), &mut left_cover);
+        let (rounds, solved) = solve(conf, &map);
+        if solved && rounds.len() >= 6 {
+            return Some(rounds[rounds.len() / 2].clone());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{generate, solvable};
+    use crate::board;
+    use crate::config::{GameConfig, GameMode};
+
+    #[test]
+    fn generated_puzzles_need_no_guess() {
+        let conf = GameConfig::from_game_mode(GameMode::Simple);
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..5 {
+            let map = generate(&conf, &mut rng).unwrap();
+            assert!(solvable(&conf, &map));
+            assert!(map.iter().any(|c| c.surface == crate::map::Surface::Open));
+        }
+    }
+
+    #[test]
+    fn give_up_without_puzzles() {
+        // Every cell but one is a mine, nothing is left to deduce.
+        let conf = GameConfig {
+            bomb: 80,
+            ..GameConfig::from_game_mode(GameMode::Simple)
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        assert!(generate(&conf, &mut rng).is_none());
+    }
+
+    #[test]
+    fn a_fifty_fifty_is_no_puzzle() {
+        let (conf, map) = board::from_text("oo\n*.\n").unwrap();
+        assert!(!solvable(&conf, &map));
+        let (conf, map) = board::from_text("oo.\nooo\nooo\n*oo\n").unwrap();
+        assert!(solvable(&conf, &map));
+    }
+}
@@ -0,0 +1,752 @@
+//! Reason about where the mines are, only from what a player can see.
+//!
+//! Flags are never trusted, a flagged cell is as unknown as a covered one.
+//!
+//! Where cells hold up to N mines, every way to put the mines is as likely, the way boards are made,
+//! and a probability is that of a cell holding any mine.
+
+use std::collections::HashMap;
+
+use crate::{config, map};
+
+/// A number on the board: `mines` mines hide among these unknown `cells`.
+struct Constraint {
+    cells: Vec<usize>,
+    mines: usize,
+}
+
+/// Unknown cells touched by exactly the same constraints, they always share a probability.
+struct Group {
+    cells: Vec<usize>,
+    constraints: Vec<usize>,
+}
+
+/// How the mines of one independent part of the frontier can be placed.
+struct Component {
+    groups: Vec<usize>,
+    /// Weighted number of solutions with k mines.
+    counts: Vec<f64>,
+    /// Weighted number of cells with mines in each group (same order as `groups`), for solutions with k mines.
+    group_mines: Vec<Vec<f64>>,
+    /// Too big to enumerate, only estimated, so nothing about it is sure.
+    estimated: bool,
+}
+
+/// All the numbers next to unknown cells.
+fn constraints(conf: &config::GameConfig, map: &[map::Cell]) -> Vec<Constraint> {
+    let mut constraints = vec![];
+    for (pos, cell) in map.iter().enumerate() {
+        if cell.surface != map::Surface::Open || cell.content.is_mine() {
+            continue;
+        }
+        let mut cells = vec![];
+        map::check_around_fn(conf, pos, |pos| {
+            if map[pos].surface != map::Surface::Open {
+                cells.push(pos);
+            }
+        });
+        if !cells.is_empty() {
+            let mines = cell.content.count();
+            constraints.push(Constraint { cells, mines });
+        }
+    }
+    constraints
+}
+
+/// Group the frontier cells by the constraints touching them.
+fn groups(constraints: &[Constraint]) -> Vec<Group> {
+    let mut touched: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, constraint) in constraints.iter().enumerate() {
+        for &pos in &constraint.cells {
+            touched.entry(pos).or_default().push(idx);
+        }
+    }
+    let mut by_constraints: HashMap<Vec<usize>, Vec<usize>> = HashMap::new();
+    for (pos, constraints) in touched {
+        by_constraints.entry(constraints).or_default().push(pos);
+    }
+    let mut groups: Vec<Group> = by_constraints
+        .into_iter()
+        .map(|(constraints, mut cells)| {
+            cells.sort_unstable();
+            Group { cells, constraints }
+        })
+        .collect();
+    groups.sort_unstable_by_key(|group| group.cells[0]);
+    groups
+}
+
+fn find(parent: &mut [usize], idx: usize) -> usize {
+    let mut root = idx;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[idx] = root;
+    root
+}
+
+/// Split the groups into components that share no constraint.
+fn split(constraints: &[Constraint], groups: &[Group]) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..constraints.len()).collect();
+    for group in groups {
+        let first = find(&mut parent, group.constraints[0]);
+        for &other in &group.constraints[1..] {
+            let other = find(&mut parent, other);
+            parent[other] = first;
+        }
+    }
+    let mut components: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, group) in groups.iter().enumerate() {
+        let root = find(&mut parent, group.constraints[0]);
+        components.entry(root).or_default().push(idx);
+    }
+    let mut components: Vec<Vec<usize>> = components.into_values().collect();
+    components.sort_unstable();
+    components
+}
+
+/// Weights by a number of mines, the first one for `low` mines.
+#[derive(Clone, Default)]
+struct Poly {
+    low: usize,
+    weights: Vec<f64>,
+}
+
+impl Poly {
+    fn one() -> Poly {
+        Poly {
+            low: 0,
+            weights: vec![1.0],
+        }
+    }
+
+    /// Add `other` with `shift` more mines, times `scale`.
+    fn add(&mut self, other: &Poly, shift: usize, scale: f64) {
+        let low = other.low + shift;
+        if self.weights.is_empty() {
+            self.low = low;
+        }
+        if low < self.low {
+            let mut weights = vec![0.0; self.low - low];
+            weights.append(&mut self.weights);
+            self.weights = weights;
+            self.low = low;
+        }
+        let end = low - self.low + other.weights.len();
+        if end > self.weights.len() {
+            self.weights.resize(end, 0.0);
+        }
+        for (idx, w) in other.weights.iter().enumerate() {
+            self.weights[low - self.low + idx] += w * scale;
+        }
+    }
+}
+
+/// The mines placed in the constraints still open, after some groups of a component.
+type State = Vec<u8>;
+
+/// Walk the groups along the frontier: the next one shares the most constraints already
+/// started, so few constraints are half decided at any time.
+fn order(groups: &[Group], members: &[usize]) -> Vec<usize> {
+    let mut left = members.to_vec();
+    let mut started = vec![];
+    let mut order = vec![];
+    while !left.is_empty() {
+        let (idx, _) = left
+            .iter()
+            .enumerate()
+            .max_by_key(|&(idx, &g)| {
+                let shared = groups[g]
+                    .constraints
+                    .iter()
+                    .filter(|c| started.contains(c))
+                    .count();
+                (shared, std::cmp::Reverse(idx))
+            })
+            .unwrap();
+        let g = left.remove(idx);
+        started.extend(&groups[g].constraints);
+        order.push(g);
+    }
+    order
+}
+
+/// The most states `solve` keeps for a component, past it the component is only estimated.
+const MAX_STATES: usize = 300_000;
+
+/// A rough look at a component too big to enumerate, like a wide frontier through layers:
+/// each group is as full as its constraints on average, with the mines spread over the cells.
+fn estimate(
+    constraints: &[Constraint],
+    groups: &[Group],
+    members: &[usize],
+    stack: usize,
+) -> Component {
+    let shares: Vec<f64> = members
+        .iter()
+        .map(|&g| {
+            let shares: Vec<f64> = groups[g]
+                .constraints
+                .iter()
+                .map(|&c| constraints[c].mines as f64 / (constraints[c].cells.len() * stack) as f64)
+                .collect();
+            if shares.iter().any(|&s| s <= 0.0) {
+                0.0
+            } else if shares.iter().any(|&s| s >= 1.0) {
+                1.0
+            } else {
+                shares.iter().sum::<f64>() / shares.len() as f64
+            }
+        })
+        .collect();
+    let sizes: Vec<usize> = members.iter().map(|&g| groups[g].cells.len()).collect();
+    let max_mines = sizes.iter().sum::<usize>() * stack;
+    // All the weight on the mines expected.
+    let mines = shares
+        .iter()
+        .zip(&sizes)
+        .map(|(share, &size)| share * (size * stack) as f64)
+        .sum::<f64>()
+        .round() as usize;
+    let mut counts = vec![0.0; max_mines + 1];
+    counts[mines.min(max_mines)] = 1.0;
+    let group_mines = shares
+        .iter()
+        .zip(&sizes)
+        .map(|(share, &size)| {
+            // A cell has a mine unless each of its `stack` places is empty.
+            let mut filled = vec![0.0; max_mines + 1];
+            filled[mines.min(max_mines)] = (1.0 - (1.0 - share).powi(stack as i32)) * size as f64;
+            filled
+        })
+        .collect();
+    Component {
+        groups: members.to_vec(),
+        counts,
+        group_mines,
+        estimated: true,
+    }
+}
+
+/// Enumerate every way to place mines in a component.
+///
+/// Groups are decided one at a time, and ways that reach the same mines in every constraint
+/// are merged, so a long frontier costs its length rather than its number of solutions.
+/// With more than `max_states` it's only estimated.
+fn solve(
+    constraints: &[Constraint],
+    groups: &[Group],
+    members: &[usize],
+    stack: usize,
+    ways: &Ways,
+    max_states: usize,
+) -> Component {
+    let max_mines: usize = members
+        .iter()
+        .map(|&g| groups[g].cells.len())
+        .sum::<usize>()
+        * stack;
+
+    let members = &order(groups, members);
+    // The constraints of the component, by a local index.
+    let mut local: HashMap<usize, usize> = HashMap::new();
+    for &g in members {
+        for &c in &groups[g].constraints {
+            let next = local.len();
+            local.entry(c).or_insert(next);
+        }
+    }
+    let mut open = vec![0; local.len()];
+    for &g in members {
+        for c in &groups[g].constraints {
+            open[local[c]] += groups[g].cells.len();
+        }
+    }
+    // For each group, its constraints, their mines and their cells still open after it.
+    let steps: Vec<Vec<(usize, usize, usize)>> = members
+        .iter()
+        .map(|&g| {
+            groups[g]
+                .constraints
+                .iter()
+                .map(|c| {
+                    open[local[c]] -= groups[g].cells.len();
+                    (local[c], constraints[*c].mines, open[local[c]])
+                })
+                .collect()
+        })
+        .collect();
+    // The constraints started and not finished after each group, the ones a state holds.
+    let mut last = vec![0; local.len()];
+    for (idx, step) in steps.iter().enumerate() {
+        for &(c, _, _) in step {
+            last[c] = idx;
+        }
+    }
+    let mut live: Vec<Vec<usize>> = vec![vec![]];
+    for (idx, step) in steps.iter().enumerate() {
+        let mut now: Vec<usize> = live[idx]
+            .iter()
+            .chain(step.iter().map(|(c, _, _)| c))
+            .cloned()
+            .filter(|&c| last[c] > idx)
+            .collect();
+        now.sort_unstable();
+        now.dedup();
+        live.push(now);
+    }
+    // Where each constraint of a state comes from in the state before, and if the group touches it.
+    let slot = |idx: usize, c: usize| live[idx].binary_search(&c).ok();
+    let moves: Vec<Vec<(Option<usize>, bool)>> = (0..steps.len())
+        .map(|idx| {
+            live[idx + 1]
+                .iter()
+                .map(|&c| (slot(idx, c), steps[idx].iter().any(|s| s.0 == c)))
+                .collect()
+        })
+        .collect();
+    let checks: Vec<Vec<(Option<usize>, usize, usize)>> = steps
+        .iter()
+        .enumerate()
+        .map(|(idx, step)| {
+            step.iter()
+                .map(|&(c, mines, open)| (slot(idx, c), mines, open))
+                .collect()
+        })
+        .collect();
+    let next = |idx: usize, state: &State, placed: usize| -> Option<State> {
+        let before = |slot: Option<usize>| slot.map_or(0, |i| state[i] as usize);
+        for &(slot, mines, open) in &checks[idx] {
+            let now = before(slot) + placed;
+            if now > mines || now + open * stack < mines {
+                return None;
+            }
+        }
+        let state = moves[idx]
+            .iter()
+            .map(|&(slot, touched)| (before(slot) + if touched { placed } else { 0 }) as u8);
+        Some(state.collect())
+    };
+
+    // The weight of the ways to reach each state, by mines placed so far.
+    let mut forward: Vec<HashMap<State, Poly>> = vec![HashMap::new(); members.len() + 1];
+    forward[0].insert(vec![], Poly::one());
+    let mut kept = 1;
+    for (idx, &g) in members.iter().enumerate() {
+        let size = groups[g].cells.len();
+        let (done, todo) = forward.split_at_mut(idx + 1);
+        for (state, poly) in &done[idx] {
+            for placed in 0..=size * stack {
+                if let Some(state) = next(idx, state, placed) {
+                    let weight = ways.count[size][placed];
+                    todo[0].entry(state).or_default().add(poly, placed, weight);
+                }
+            }
+        }
+        kept += todo[0].len();
+        if kept > max_states {
+            return estimate(constraints, groups, members, stack);
+        }
+    }
+
+    // The same from the end, only for states that lead to a solution.
+    let mut backward: HashMap<State, Poly> = forward[members.len()]
+        .keys()
+        .map(|state| (state.clone(), Poly::one()))
+        .collect();
+    let mut group_mines = vec![vec![0.0; max_mines + 1]; members.len()];
+    for (idx, &g) in members.iter().enumerate().rev() {
+        let size = groups[g].cells.len();
+        let mut before: HashMap<State, Poly> = HashMap::new();
+        for (state, head) in &forward[idx] {
+            for placed in 0..=size * stack {
+                let Some(tail) = next(idx, state, placed).and_then(|s| backward.get(&s)) else {
+                    continue;
+                };
+                let weight = ways.count[size][placed];
+                before
+                    .entry(state.clone())
+                    .or_default()
+                    .add(tail, placed, weight);
+                let filled = weight * ways.filled[size][placed];
+                for (i, h) in head.weights.iter().enumerate() {
+                    for (j, t) in tail.weights.iter().enumerate() {
+                        group_mines[idx][head.low + placed + tail.low + i + j] += h * t * filled;
+                    }
+                }
+            }
+        }
+        backward = before;
+    }
+
+    let mut counts = vec![0.0; max_mines + 1];
+    for poly in backward.values() {
+        for (idx, w) in poly.weights.iter().enumerate() {
+            counts[poly.low + idx] += w;
+        }
+    }
+
+    // Keep numbers small, only the ratios matter.
+    let scale = counts.iter().cloned().fold(0.0, f64::max);
+    if scale > 0.0 {
+        counts.iter_mut().for_each(|v| *v /= scale);
+        group_mines.iter_mut().flatten().for_each(|v| *v /= scale);
+    }
+    Component {
+        groups: members.to_vec(),
+        counts,
+        group_mines,
+        estimated: false,
+    }
+}
+
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut out = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            out[i + j] += x * y;
+        }
+    }
+    out
+}
+
+/// How mines can sit in a group of cells, by its size and its mines.
+struct Ways {
+    /// The ways to put them, C(size, mines) with one mine a cell.
+    count: Vec<Vec<f64>>,
+    /// How many of the cells have a mine, on average over the ways.
+    filled: Vec<Vec<f64>>,
+}
+
+impl Ways {
+    fn new(largest: usize, stack: usize) -> Ways {
+        // Added up like Pascal's triangle, so the counts stay exact while they are small.
+        let mut count = vec![vec![0.0; largest * stack + 1]; largest + 1];
+        count[0][0] = 1.0;
+        for n in 1..=largest {
+            for k in 0..=largest * stack {
+                count[n][k] = (0..=stack.min(k)).map(|c| count[n - 1][k - c]).sum();
+            }
+        }
+        // A cell is empty in the ways for the other cells to hold all the mines,
+        // written so that it's exactly k with one mine a cell.
+        let filled = (0..=largest)
+            .map(|n| {
+                (0..=largest * stack)
+                    .map(|k| match n {
+                        0 => 0.0,
+                        _ if count[n][k] == 0.0 => 0.0,
+                        _ => n as f64 * (count[n][k] - count[n - 1][k]) / count[n][k],
+                    })
+                    .collect()
+            })
+            .collect();
+        Ways { count, filled }
+    }
+}
+
+/// ln(n!) for n in 0..=max.
+fn ln_factorials(max: usize) -> Vec<f64> {
+    let mut table = vec![0.0; max + 1];
+    for n in 1..=max {
+        table[n] = table[n - 1] + (n as f64).ln();
+    }
+    table
+}
+
+/// The exact probability of a mine under each unknown (covered or flagged) cell,
+/// None for opened cells. A part of the frontier too big to enumerate is only estimated,
+/// and then neither its cells nor the cells away from the frontier are ever sure.
+pub fn probabilities(conf: &config::GameConfig, map: &[map::Cell]) -> Vec<Option<f64>> {
+    probabilities_within(conf, map, MAX_STATES)
+}
+
+/// Like `probabilities`, estimating the components with more than `max_states`.
+fn probabilities_within(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    max_states: usize,
+) -> Vec<Option<f64>> {
+    let constraints = constraints(conf, map);
+    let groups = groups(&constraints);
+    let components = split(&constraints, &groups);
+
+    let largest = groups.iter().map(|g| g.cells.len()).max().unwrap_or(0);
+    let ways = Ways::new(largest, conf.stack);
+    let components: Vec<Component> = components
+        .iter()
+        .map(|members| {
+            solve(
+                &constraints,
+                &groups,
+                members,
+                conf.stack,
+                &ways,
+                max_states,
+            )
+        })
+        .collect();
+    // An estimate only keeps its cells and the rest from looking sure.
+    let unsure = |p: f64| p.clamp(UNSURE, 1.0 - UNSURE);
+    let estimated = components.iter().any(|c| c.estimated);
+
+    let unknown = map
+        .iter()
+        .filter(|cell| cell.surface != map::Surface::Open)
+        .count();
+    let frontier: usize = groups.iter().map(|g| g.cells.len()).sum();
+    let rest = unknown - frontier;
+    let mines = conf.bomb;
+
+    // The weight of k mines on the frontier is the number of ways to put the others elsewhere.
+    let ln_fact = ln_factorials(unknown);
+    let ln_rest = (conf.stack > 1).then(|| map::ln_ways(rest, mines, conf.stack));
+    let ln_choose = |n: usize, k: usize| match &ln_rest {
+        Some(ln) => ln[n][k],
+        None => ln_fact[n] - ln_fact[k] - ln_fact[n - k],
+    };
+    let all = components
+        .iter()
+        .fold(vec![1.0], |acc, c| convolve(&acc, &c.counts));
+    let ln_weights: Vec<Option<f64>> = (0..all.len())
+        .map(|k| {
+            let left = mines.checked_sub(k)?;
+            (left <= rest * conf.stack).then(|| ln_choose(rest, left))
+        })
+        .collect();
+    let top = ln_weights
+        .iter()
+        .flatten()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = ln_weights
+        .iter()
+        .map(|w| w.map_or(0.0, |w| (w - top).exp()))
+        .collect();
+
+    let total: f64 = all.iter().zip(&weights).map(|(c, w)| c * w).sum();
+    let mut probs: Vec<Option<f64>> = map
+        .iter()
+        .map(|cell| (cell.surface != map::Surface::Open).then_some(0.0))
+        .collect();
+    if total <= 0.0 {
+        // The board tells lies, nothing better than a uniform guess.
+        let uniform = (mines as f64 / unknown.max(1) as f64).min(1.0);
+        probs.iter_mut().flatten().for_each(|p| *p = uniform);
+        return probs;
+    }
+
+    // The share of the other cells with a mine, when they hold `left` mines.
+    let filled_rest = |left: usize| match &ln_rest {
+        Some(ln) if left <= rest * conf.stack => 1.0 - (ln[rest - 1][left] - ln[rest][left]).exp(),
+        Some(_) => 0.0,
+        None => left as f64 / rest as f64,
+    };
+    let rest_prob = if rest == 0 {
+        0.0
+    } else {
+        all.iter()
+            .zip(&weights)
+            .enumerate()
+            .map(|(k, (c, w))| c * w * filled_rest(mines.saturating_sub(k)))
+            .sum::<f64>()
+            / total
+    };
+    for p in probs.iter_mut().flatten() {
+        *p = if estimated {
+            unsure(rest_prob)
+        } else {
+            rest_prob
+        };
+    }
+
+    for (idx, component) in components.iter().enumerate() {
+        let others = components
+            .iter()
+            .enumerate()
+            .filter(|&(other, _)| other != idx)
+            .fold(vec![1.0], |acc, (_, c)| convolve(&acc, &c.counts));
+        // The weight of this component having k mines, summed over the others.
+        let outer: Vec<f64> = (0..component.counts.len())
+            .map(|k| {
+                others
+                    .iter()
+                    .enumerate()
+                    .map(|(j, c)| c * weights.get(k + j).copied().unwrap_or(0.0))
+                    .sum()
+            })
+            .collect();
+        for (member, &g) in component.groups.iter().enumerate() {
+            let group = &groups[g];
+            let mines: f64 = component.group_mines[member]
+                .iter()
+                .zip(&outer)
+                .map(|(m, w)| m * w)
+                .sum();
+            let p = (mines / group.cells.len() as f64 / total).clamp(0.0, 1.0);
+            let p = if component.estimated { unsure(p) } else { p };
+            for &pos in &group.cells {
+                probs[pos] = Some(p);
+            }
+        }
+    }
+    probs
+}
+
+/// A probability this close to 0 or 1 is a certainty.
+pub const EPSILON: f64 = 1e-9;
+
+/// The closest to 0 or 1 an estimated probability gets, far from a certainty.
+const UNSURE: f64 = 1e-6;
+
+pub fn is_safe(p: f64) -> bool {
+    p < EPSILON
+}
+
+pub fn is_mine(p: f64) -> bool {
+    p > 1.0 - EPSILON
+}
+
+/// What a hint points at.
+#[derive(Debug, PartialEq)]
+pub enum Hint {
+    /// This covered cell cannot be a mine.
+    Safe(usize),
+    /// This covered cell must be a mine.
+    Mine(usize),
+    /// Nothing is sure, this cell is the least likely to be a mine.
+    Guess(usize, f64),
+}
+
+/// The distance between two cells, as a king walks, through the layers too.
+fn distance(conf: &config::GameConfig, a: usize, b: usize) -> usize {
+    let size = conf.layer_size();
+    let (al, ar, ac) = (a / size, a % size / conf.width, a % conf.width);
+    let (bl, br, bc) = (b / size, b % size / conf.width, b % conf.width);
+    al.abs_diff(bl).max(ar.abs_diff(br)).max(ac.abs_diff(bc))
+}
+
+/// Find the most useful covered cell, the nearest to `near` among equals.
+pub fn hint(conf: &config::GameConfig, map: &[map::Cell], near: usize) -> Option<Hint> {
+    hint_from(conf, map, &probabilities(conf, map), near)
+}
+
+/// Like `hint`, with the probabilities already known.
+pub fn hint_from(
+    conf: &config::GameConfig,
+    map: &[map::Cell],
+    probs: &[Option<f64>],
+    near: usize,
+) -> Option<Hint> {
+    let covered = || {
+        probs
+            .iter()
+            .enumerate()
+            .filter_map(|(pos, p)| match map[pos].surface {
+                map::Surface::Cover => p.map(|p| (pos, p)),
+                _ => None,
+            })
+    };
+    let nearest = |(pos, _): &(usize, f64)| distance(conf, *pos, near);
+    if let Some((pos, _)) = covered().filter(|&(_, p)| is_safe(p)).min_by_key(nearest) {
+        return Some(Hint::Safe(pos));
+    }
+    if let Some((pos, _)) = covered().filter(|&(_, p)| is_mine(p)).min_by_key(nearest) {
+        return Some(Hint::Mine(pos));
+    }
+    covered()
+        .min_by(|a, b| a.1.total_cmp(&b.1).then(nearest(a).cmp(&nearest(b))))
+        .map(|(pos, p)| Hint::Guess(pos, p))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hint, hint_from, is_mine, is_safe, probabilities, probabilities_within, Hint};
+    use crate::config::GameConfig;
+    use crate::map::{self, Surface};
+
+    /// A map from rows of '*' (mine) and '.' (safe), opening the cells in `open`.
+    fn board(rows: &[&str], bomb: usize, open: &[usize]) -> (GameConfig, Vec<map::Cell>) {
+        let conf = GameConfig::sized(rows.len(), rows[0].len(), bomb);
+        let mines: Vec<bool> = rows
+            .iter()
+            .flat_map(|r| r.chars().map(|c| c == '*'))
+            .collect();
+        let mut map = map::from_mines(&conf, &mines);
+        for &pos in open {
+            map[pos].surface = Surface::Open;
+        }
+        (conf, map)
+    }
+
+    #[test]
+    fn one_two_one() {
+        // 1 2 1 on the bottom wall: mines above the ones.
+        let (conf, map) = board(&["*.*", "..."], 2, &[3, 4, 5]);
+        let probs = probabilities(&conf, &map);
+        assert!(is_mine(probs[0].unwrap()));
+        assert!(is_safe(probs[1].unwrap()));
+        assert!(is_mine(probs[2].unwrap()));
+        assert_eq!(None, probs[4]);
+    }
+
+    #[test]
+    fn global_mine_count() {
+        // A lonely 1 between two cells, with one mine left elsewhere or not.
+        let (conf, map) = board(&["*.", "..", ".."], 1, &[2, 3, 4, 5]);
+        let probs = probabilities(&conf, &map);
+        assert!((probs[0].unwrap() - 0.5).abs() < 1e-9);
+        assert!((probs[1].unwrap() - 0.5).abs() < 1e-9);
+
+        let (conf, map) = board(&["*.", "..", "..", ".*"], 2, &[2, 3, 4, 5]);
+        let probs = probabilities(&conf, &map);
+        let top = probs[0].unwrap() + probs[1].unwrap();
+        assert!((top - 1.0).abs() < 1e-9);
+        assert!((probs[6].unwrap() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hint_nearest_safe() {
+        let (conf, map) = board(&["*.*", "..."], 2, &[3, 4, 5]);
+        assert_eq!(Some(Hint::Safe(1)), hint(&conf, &map, 0));
+
+        let (conf, mut map) = board(&["*.*", "..."], 2, &[1, 3, 4, 5]);
+        map[2].surface = crate::map::Surface::Flag(1);
+        assert_eq!(Some(Hint::Mine(0)), hint(&conf, &map, 5));
+    }
+
+    #[test]
+    fn stacked_mines() {
+        // A 2 between two cells holding up to 2 mines each, the last mine alone at the end:
+        // 2+0, 1+1 and 0+2 are as likely.
+        let conf = GameConfig {
+            stack: 2,
+            ..GameConfig::sized(1, 4, 3)
+        };
+        let mut map = map::from_counts(&conf, &[2, 0, 0, 1]);
+        map[1].surface = Surface::Open;
+        let probs = probabilities(&conf, &map);
+        assert!((probs[0].unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert!((probs[2].unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert!(is_mine(probs[3].unwrap()));
+    }
+
+    #[test]
+    fn estimates_are_never_sure() {
+        // 1 2 1 on the bottom wall, with two cells away from the numbers: exact, those are safe.
+        let (conf, map) = board(&["*.*..", "....."], 2, &[5, 6, 7]);
+        let probs = probabilities(&conf, &map);
+        assert!(is_safe(probs[4].unwrap()));
+        assert!(is_mine(probs[0].unwrap()));
+
+        // With no room for a single state, the frontier is estimated and nothing is sure.
+        let probs = probabilities_within(&conf, &map, 1);
+        for p in probs.iter().flatten() {
+            assert!(!is_safe(*p) && !is_mine(*p), "{}", p);
+        }
+        assert!(matches!(
+            hint_from(&conf, &map, &probs, 0),
+            Some(Hint::Guess(..))
+        ));
+    }
+}
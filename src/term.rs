@@ -0,0 +1,161 @@
+use std::io::{self, Stdout, Write};
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+use std::{panic, process, thread};
+
+use termion::event::Event;
+use termion::input::{MouseTerminal, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::{AlternateScreen, IntoAlternateScreen};
+
+/// The terminal the whole game is drawn on.
+pub type Terminal = MouseTerminal<AlternateScreen<RawTerminal<Stdout>>>;
+
+/// Turn on bracketed paste, so pasted text can be told from typed keys.
+const PASTE_ON: &str = "\x1b[?2004h";
+
+/// Leave mouse mode and bracketed paste, show the cursor and go back to the main screen.
+const RESTORE_SEQUENCE: &str =
+    "\x1b[?2004l\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l\x1b[?25h\x1b[?1049l";
+
+/// The terminal attributes before entering raw mode.
+static ORIGINAL: OnceLock<libc::termios> = OnceLock::new();
+
+/// Enter raw mode, mouse mode, bracketed paste and the alternate screen.
+///
+/// A panic hook and SIGINT/SIGTERM handlers are installed too, so the shell is
+/// always given back in a usable state.
+pub fn setup() -> io::Result<Terminal> {
+    let mut attrs = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut attrs) } == 0 {
+        let _ = ORIGINAL.set(attrs);
+    }
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore();
+        default_hook(info);
+        process::exit(101);
+    }));
+    for sig in [libc::SIGINT, libc::SIGTERM] {
+        unsafe { libc::signal(sig, on_signal as *const () as libc::sighandler_t) };
+    }
+
+    let mut stdout = io::stdout().into_raw_mode()?.into_alternate_screen()?;
+    write!(stdout, "{}", PASTE_ON)?;
+    Ok(MouseTerminal::from(stdout))
+}
+
+/// Give the terminal back to the shell, only async-signal-safe calls here.
+pub fn restore() {
+    unsafe {
+        libc::write(
+            libc::STDOUT_FILENO,
+            RESTORE_SEQUENCE.as_ptr() as *const libc::c_void,
+            RESTORE_SEQUENCE.len(),
+        );
+        if let Some(attrs) = ORIGINAL.get() {
+            libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, attrs);
+        }
+    }
+}
+
+/// Gives the terminal back when dropped, so a quit or an error leaves no bracketed paste behind.
+/// Keep it next to the terminal of `setup`, made after it so it goes first.
+pub struct Restore;
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+extern "C" fn on_signal(sig: libc::c_int) {
+    restore();
+    unsafe {
+        libc::signal(sig, libc::SIG_DFL);
+        libc::raise(sig);
+    }
+}
+
+/// Terminal events, read on a thread of their own so they can be waited for with a timeout.
+pub struct Input {
+    events: mpsc::Receiver<io::Result<Event>>,
+}
+
+impl Input {
+    /// Start reading stdin, for the whole life of the program.
+    pub fn spawn() -> Input {
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || {
+            for evt in io::stdin().events() {
+                if tx.send(evt).is_err() {
+                    break;
+                }
+            }
+        });
+        Input { events }
+    }
+
+    /// Every event, blocking until the next one.
+    pub fn events(&self) -> mpsc::Iter<'_, io::Result<Event>> {
+        self.events.iter()
+    }
+
+    /// The next event if one comes within timeout.
+    pub fn wait(&self, timeout: Duration) -> io::Result<Option<Event>> {
+        match self.events.recv_timeout(timeout) {
+            Ok(evt) => evt.map(Some),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stdin is closed",
+            )),
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Put text on the system clipboard with the OSC 52 escape sequence,
+/// passed through tmux when running in it. Terminals that don't know it ignore it.
+pub fn copy(out: &mut impl Write, text: &str) -> io::Result<()> {
+    let osc = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if std::env::var_os("TMUX").is_some() {
+        write!(out, "\x1bPtmux;\x1b{}\x1b\\", osc)?;
+    } else {
+        write!(out, "{}", osc)?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::base64;
+
+    #[test]
+    fn base64_pads() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("8J+aqQ==", base64("🚩".as_bytes()));
+    }
+}